@@ -0,0 +1,94 @@
+//! Throughput of a `WorkerPool` by number of isolates, for a CPU bound worker.
+//!
+//! ```bash
+//! cargo bench --bench isolates
+//! cargo bench --bench isolates -- 1 2 4 8 16
+//! ```
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::future::join_all;
+use openworkers_runtime::FetchInit;
+use openworkers_runtime::Task;
+use openworkers_runtime::WorkerPool;
+use tokio::sync::oneshot;
+
+const REQUESTS: usize = 2000;
+
+const WORKER: &str = r#"
+addEventListener("fetch", (event) => {
+  let hash = 0;
+
+  for (let i = 0; i < 100_000; i++) {
+    hash = (hash * 31 + i) | 0;
+  }
+
+  event.respondWith(new Response(String(hash)));
+});
+"#;
+
+async fn request(pool: &WorkerPool) {
+    let (res_tx, res_rx) = oneshot::channel();
+
+    let req = http_v02::Request::builder()
+        .uri("http://localhost/")
+        .body(Default::default())
+        .unwrap();
+
+    let task = Task::Fetch(Some(FetchInit::new(req, res_tx)));
+
+    let (result, res) = tokio::join!(pool.exec(task), res_rx);
+
+    result.unwrap();
+    assert_eq!(res.unwrap().status(), 200);
+}
+
+async fn bench(isolates: usize) {
+    let script = openworkers_runtime::Script {
+        specifier: openworkers_runtime::worker_url("bench"),
+        code: Some(WORKER.to_string().into()),
+        env: None,
+    };
+
+    let pool = WorkerPool::new(isolates, script, None, Arc::new(Default::default)).unwrap();
+
+    // Warm up every isolate
+    join_all((0..isolates).map(|_| request(&pool))).await;
+
+    let start = Instant::now();
+
+    join_all((0..REQUESTS).map(|_| request(&pool))).await;
+
+    let elapsed = start.elapsed();
+
+    println!(
+        "{isolates:>3} isolates: {REQUESTS} requests in {:>6}ms, {:>8.0} req/s",
+        elapsed.as_millis(),
+        REQUESTS as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`
+    let mut isolates: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .collect();
+
+    if isolates.is_empty() {
+        let cores = std::thread::available_parallelism().map_or(4, |n| n.get());
+        isolates = vec![1, 2, 4, cores, cores * 2];
+        isolates.sort();
+        isolates.dedup();
+    }
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    for isolates in isolates {
+        rt.block_on(bench(isolates));
+    }
+}
@@ -1,18 +1,37 @@
 use std::fs::File;
 use std::path::PathBuf;
 
-const RUNTIME_SNAPSHOT_PATH: &str = env!("RUNTIME_SNAPSHOT_PATH");
+/// Snapshot path used when `RUNTIME_SNAPSHOT_PATH` is not set.
+const DEFAULT_SNAPSHOT_FILE: &str = "openworkers-runtime-snapshot.bin";
 
 fn main () {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=src/ext/*");
     println!("cargo:rerun-if-changed=src/runtime.rs");
     println!("cargo:rerun-if-changed=src/extensions.rs");
-    
-    let path = PathBuf::from(RUNTIME_SNAPSHOT_PATH);
+    println!("cargo:rerun-if-env-changed=RUNTIME_SNAPSHOT_PATH");
+
+    let path = match std::env::var_os("RUNTIME_SNAPSHOT_PATH") {
+        Some(path) => PathBuf::from(path),
+        None => std::env::temp_dir().join(DEFAULT_SNAPSHOT_FILE),
+    };
+
+    // Read with `env!` by the crate (snapshot binary and embedded snapshot)
+    println!("cargo:rustc-env=RUNTIME_SNAPSHOT_PATH={}", path.display());
+
+    // No snapshot embedded, the runtime always loads its ES modules
+    if std::env::var_os("CARGO_FEATURE_NO_SNAPSHOT").is_some() {
+        return;
+    }
 
     // Create the file if it doesn't exist
     if !path.exists() {
-        File::create(&path).unwrap();
+        if let Err(err) = File::create(&path) {
+            panic!(
+                "failed to create the runtime snapshot file {}: {err}\n\
+                 set RUNTIME_SNAPSHOT_PATH to a writable path or enable the `no-snapshot` feature",
+                path.display()
+            );
+        }
     }
 }
@@ -0,0 +1,33 @@
+addEventListener("fetch", (event) => {
+  event.respondWith(
+    handleRequest(event.request).catch(
+      (err) => new Response(err.stack, { status: 500 })
+    )
+  );
+});
+
+const upstreams = [
+  "https://echo.workers.rocks/data.json",
+  "https://echo.workers.rocks/headers.json",
+];
+
+async function handleRequest(request) {
+  // Start all upstream requests concurrently
+  const responses = upstreams.map((url) => fetch(url));
+
+  const { readable, writable } = new TransformStream();
+
+  // Pipe upstream bodies one after the other, without buffering them
+  (async () => {
+    for (const response of responses) {
+      const { body } = await response;
+      await body.pipeTo(writable, { preventClose: true });
+    }
+
+    await writable.close();
+  })();
+
+  return new Response(readable, {
+    headers: { "content-type": "application/json" },
+  });
+}
@@ -2,6 +2,7 @@ use log::debug;
 use log::error;
 use openworkers_runtime::module_url;
 use openworkers_runtime::ScheduledInit;
+use openworkers_runtime::ScheduledOutcome;
 use openworkers_runtime::Script;
 use openworkers_runtime::Task;
 use openworkers_runtime::Worker;
@@ -33,11 +34,19 @@ async fn main() -> Result<(), ()> {
         path
     };
 
-    let (res_tx, res_rx) = oneshot::channel::<()>();
+    let (res_tx, res_rx) = oneshot::channel::<ScheduledOutcome>();
     let (end_tx, end_rx) =  oneshot::channel::<()>();
 
+    let specifier = match module_url(file_path.as_str()) {
+        Ok(specifier) => specifier,
+        Err(err) => {
+            eprintln!("invalid path {}: {}", file_path, err);
+            std::process::exit(1);
+        }
+    };
+
     let script = Script {
-        specifier: module_url(file_path.as_str()),
+        specifier,
         code: None,
         env: None
     };
@@ -51,7 +60,7 @@ async fn main() -> Result<(), ()> {
         let local = tokio::task::LocalSet::new();
 
         local.spawn_local(async move {
-            let mut worker = Worker::new(script, None).await.unwrap();
+            let mut worker = Worker::new(script, None, Default::default()).await.unwrap();
 
             match worker
                 .exec(Task::Scheduled(Some(ScheduledInit::new(res_tx, time))))
@@ -79,7 +88,8 @@ async fn main() -> Result<(), ()> {
         _ = tokio::signal::ctrl_c() => debug!("ctrl-c received"),
         // wait for task completion signal
         done = res_rx => match done {
-            Ok(()) => debug!("task completed"),
+            Ok(outcome) if outcome.retry => debug!("task asked to retry in {}s", outcome.delay_secs),
+            Ok(_) => debug!("task completed"),
             Err(err) => error!("task did not complete: {err}"),
         }
     }
@@ -3,6 +3,8 @@ use bytes::Bytes;
 use log::debug;
 use log::error;
 use openworkers_runtime::FetchInit;
+use openworkers_runtime::FileStream;
+use openworkers_runtime::ResponseBody;
 use openworkers_runtime::Script;
 use openworkers_runtime::Task;
 use openworkers_runtime::Url;
@@ -10,6 +12,8 @@ use openworkers_runtime::Worker;
 
 use tokio::sync::oneshot::channel;
 
+use futures::stream::unfold;
+
 use actix_web::{App, HttpServer};
 
 use actix_web::web;
@@ -44,7 +48,7 @@ async fn handle_request(data: Data<AppState>, req: HttpRequest) -> HttpResponse
         env: None
     };
 
-    let (res_tx, res_rx) = channel::<http_v02::Response<Bytes>>();
+    let (res_tx, res_rx) = channel::<http_v02::Response<ResponseBody>>();
     let task = Task::Fetch(Some(FetchInit::new(req, res_tx)));
 
     let handle = std::thread::spawn(move || {
@@ -52,7 +56,7 @@ async fn handle_request(data: Data<AppState>, req: HttpRequest) -> HttpResponse
 
         let tasks = local.spawn_local(async move {
             debug!("create worker");
-            let mut worker = Worker::new(script, None).await.unwrap();
+            let mut worker = Worker::new(script, None, Default::default()).await.unwrap();
 
             debug!("exec fetch task");
             match worker.exec(task).await {
@@ -80,7 +84,24 @@ async fn handle_request(data: Data<AppState>, req: HttpRequest) -> HttpResponse
                 rb.append_header((k, v));
             }
 
-            rb.body(res.body().clone())
+            match res.into_body() {
+                ResponseBody::None | ResponseBody::Empty => rb.finish(),
+                ResponseBody::Bytes(body) => rb.body(body),
+                ResponseBody::Stream(stream) => {
+                    rb.streaming(unfold(stream, |mut stream| async move {
+                        stream
+                            .recv()
+                            .await
+                            .map(|chunk| (Ok::<Bytes, actix_web::Error>(chunk), stream))
+                    }))
+                }
+                ResponseBody::File(path) => match FileStream::open(path).await {
+                    Ok(file) => rb.streaming(unfold(file, |mut file| async move {
+                        file.recv().await.map(|chunk| (chunk, file))
+                    })),
+                    Err(err) => HttpResponse::NotFound().body(err.to_string()),
+                },
+            }
         }
         Err(err) => {
             error!("worker fetch error: {}, ensure the worker registered a listener for the 'fetch' event", err);
@@ -101,6 +122,14 @@ fn get_path() -> String {
         .unwrap_or_else(|| String::from("examples/serve.js"))
 }
 
+// Number of actix worker threads, see README for the tradeoffs
+fn get_host_threads() -> usize {
+    std::env::var("HOST_THREADS")
+        .ok()
+        .and_then(|threads| threads.parse().ok())
+        .unwrap_or(4)
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     if !std::env::var("RUST_LOG").is_ok() {
@@ -126,14 +155,15 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(Data::new({
                 let path = get_path();
-                let url: Url = openworkers_runtime::module_url(path.as_str());
+                let url: Url = openworkers_runtime::module_url(path.as_str())
+                    .expect("failed to resolve worker path");
 
                 AppState { url }
             }))
             .default_service(web::to(handle_request))
     })
     .bind(("127.0.0.1", 8080))?
-    .workers(4)
+    .workers(get_host_threads())
     .run()
     .await
 }
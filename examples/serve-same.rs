@@ -1,15 +1,21 @@
+use std::sync::Arc;
+
 use bytes::Bytes;
 
 use log::debug;
 use log::error;
 use openworkers_runtime::FetchInit;
+use openworkers_runtime::FileStream;
+use openworkers_runtime::ResponseBody;
 use openworkers_runtime::Script;
 use openworkers_runtime::Task;
 use openworkers_runtime::Url;
-use openworkers_runtime::Worker;
+use openworkers_runtime::WorkerPool;
 
 use tokio::sync::oneshot::channel;
 
+use futures::stream::unfold;
+
 use actix_web::{App, HttpServer};
 
 use actix_web::web;
@@ -19,7 +25,7 @@ use actix_web::HttpResponse;
 
 struct AppState {
     url: Url,
-    task_tx: tokio::sync::mpsc::Sender<Task>,
+    pool: Arc<WorkerPool>,
 }
 
 async fn handle_request(data: Data<AppState>, req: HttpRequest) -> HttpResponse {
@@ -33,24 +39,24 @@ async fn handle_request(data: Data<AppState>, req: HttpRequest) -> HttpResponse
 
     let start = tokio::time::Instant::now();
 
-    let (res_tx, res_rx) = channel::<http_v02::Response<Bytes>>();
+    let (res_tx, res_rx) = channel::<http_v02::Response<ResponseBody>>();
 
     let req = http_v02::Request::builder()
         .uri(req.uri())
         .body(Default::default())
         .unwrap();
 
-    match data
-        .task_tx
-        .send(Task::Fetch(Some(FetchInit::new(req, res_tx))))
-        .await
-    {
-        Ok(()) => debug!("fetch task sent"),
-        Err(err) => {
-            error!("failed to send fetch task: {}", err);
-            return HttpResponse::InternalServerError().body(err.to_string());
+    // The task completes after the response was sent (e.g. streamed bodies)
+    let pool = data.pool.clone();
+    actix_web::rt::spawn(async move {
+        match pool
+            .exec(Task::Fetch(Some(FetchInit::new(req, res_tx))))
+            .await
+        {
+            Ok(()) => debug!("exec completed"),
+            Err(err) => error!("exec did not complete: {err}"),
         }
-    }
+    });
 
     let response = {
         match res_rx.await {
@@ -61,7 +67,24 @@ async fn handle_request(data: Data<AppState>, req: HttpRequest) -> HttpResponse
                     rb.append_header((k, v));
                 }
 
-                rb.body(res.body().clone())
+                match res.into_body() {
+                    ResponseBody::None | ResponseBody::Empty => rb.finish(),
+                    ResponseBody::Bytes(body) => rb.body(body),
+                    ResponseBody::Stream(stream) => {
+                        rb.streaming(unfold(stream, |mut stream| async move {
+                            stream
+                                .recv()
+                                .await
+                                .map(|chunk| (Ok::<Bytes, actix_web::Error>(chunk), stream))
+                        }))
+                    }
+                    ResponseBody::File(path) => match FileStream::open(path).await {
+                        Ok(file) => rb.streaming(unfold(file, |mut file| async move {
+                            file.recv().await.map(|chunk| (chunk, file))
+                        })),
+                        Err(err) => HttpResponse::NotFound().body(err.to_string()),
+                    },
+                }
             }
             Err(err) => {
                 error!("worker fetch error: {}, ensure the worker registered a listener for the 'fetch' event", err);
@@ -81,6 +104,22 @@ fn get_path() -> String {
         .unwrap_or_else(|| String::from("examples/serve.js"))
 }
 
+// Number of actix worker threads, see README for the tradeoffs
+fn get_host_threads() -> usize {
+    std::env::var("HOST_THREADS")
+        .ok()
+        .and_then(|threads| threads.parse().ok())
+        .unwrap_or(4)
+}
+
+// Number of isolates of the worker pool, the host threads by default
+fn get_worker_isolates() -> usize {
+    std::env::var("WORKER_ISOLATES")
+        .ok()
+        .and_then(|isolates| isolates.parse().ok())
+        .unwrap_or_else(get_host_threads)
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     if !std::env::var("RUST_LOG").is_ok() {
@@ -92,66 +131,44 @@ async fn main() -> std::io::Result<()> {
     debug!("start main");
 
     // Check that the path is correct
-    {
-        let path = get_path();
-        if !std::path::Path::new(&path).is_file() {
-            eprintln!("file not found: {}", path);
-            std::process::exit(1);
-        }
+    let path = get_path();
+
+    if !std::path::Path::new(&path).is_file() {
+        eprintln!("file not found: {}", path);
+        std::process::exit(1);
     }
 
-    println!("Listening on http://localhost:8080");
+    let url: Url =
+        openworkers_runtime::module_url(path.as_str()).expect("failed to resolve worker path");
+
+    let script = Script {
+        specifier: url.clone(),
+        code: None,
+        env: None,
+    };
+
+    let isolates = get_worker_isolates();
+
+    // Isolates run on the pool threads, shared by all host threads
+    let pool = WorkerPool::new(isolates, script, None, Arc::new(Default::default))
+        .expect("failed to start the worker pool");
+    let pool = Arc::new(pool);
+
+    println!(
+        "Listening on http://localhost:8080 ({} host threads, {isolates} isolates)",
+        get_host_threads()
+    );
 
     HttpServer::new(move || {
         App::new()
-            .app_data(Data::new({
-                let path = get_path();
-                let url: Url = openworkers_runtime::module_url(path.as_str());
-                let script = Script {
-                    specifier: url.clone(),
-                    code: None,
-                    env: None
-                };
-
-                let (task_tx, mut task_rx) = tokio::sync::mpsc::channel(1);
-
-                let _tread = std::thread::spawn(move || {
-                    let local = tokio::task::LocalSet::new();
-
-                    let tasks = local.spawn_local(async move {
-                        let mut worker = Worker::new(script, None).await.unwrap();
-
-                        loop {
-                            match task_rx.recv().await {
-                                Some(task) => match worker.exec(task).await {
-                                    Ok(()) => debug!("exec completed"),
-                                    Err(err) => error!("exec did not complete: {err}"),
-                                },
-                                None => {
-                                    debug!("task_rx closed");
-                                    break;
-                                }
-                            }
-                        }
-                    });
-
-                    let rt = tokio::runtime::Builder::new_current_thread()
-                        .enable_all()
-                        .build()
-                        .unwrap();
-
-                    match local.block_on(&rt, tasks) {
-                        Ok(()) => {}
-                        Err(err) => error!("failed to wait for end: {err}"),
-                    }
-                });
-
-                AppState { task_tx, url }
+            .app_data(Data::new(AppState {
+                url: url.clone(),
+                pool: pool.clone(),
             }))
             .default_service(web::to(handle_request))
     })
     .bind(("127.0.0.1", 8080))?
-    .workers(4)
+    .workers(get_host_threads())
     .run()
     .await
 }
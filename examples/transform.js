@@ -0,0 +1,31 @@
+addEventListener("fetch", (event) => {
+  event.respondWith(
+    handleRequest(event.request).catch(
+      (err) => new Response(err.stack, { status: 500 })
+    )
+  );
+});
+
+const upstream = "https://echo.workers.rocks/data.json";
+
+async function handleRequest(request) {
+  const response = await fetch(upstream);
+
+  // Each upstream chunk is transformed and sent as soon as it is read: the
+  // body never sits whole in the isolate, whatever its size.
+  const uppercase = new TransformStream({
+    transform(chunk, controller) {
+      controller.enqueue(chunk.toUpperCase());
+    },
+  });
+
+  const body = response.body
+    .pipeThrough(new TextDecoderStream())
+    .pipeThrough(uppercase)
+    .pipeThrough(new TextEncoderStream());
+
+  return new Response(body, {
+    status: response.status,
+    headers: { "content-type": response.headers.get("content-type") },
+  });
+}
@@ -0,0 +1,63 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use deno_core::error::type_error;
+use deno_core::error::AnyError;
+use deno_core::futures::future::LocalBoxFuture;
+use deno_core::op2;
+use deno_core::serde_json;
+use deno_core::OpState;
+use log::debug;
+
+/// Binding is a capability object exposed to the worker as a global, e.g. `SERVICE`.
+///
+/// Every method called on the global in JS is forwarded to `call` with its
+/// JSON-serializable arguments, and resolves to the returned value. Futures
+/// are polled on the worker thread so they don't need to be `Send`.
+pub trait Binding: Send + Sync {
+    /// Name of the global, it must not shadow an existing global.
+    fn name(&self) -> &str;
+
+    fn call<'a>(
+        &'a self,
+        method: &'a str,
+        args: Vec<serde_json::Value>,
+    ) -> LocalBoxFuture<'a, Result<serde_json::Value, AnyError>>;
+}
+
+/// Bindings registered by the host, looked up by name.
+#[derive(Clone, Default)]
+pub(crate) struct Bindings(pub(crate) Vec<Arc<dyn Binding>>);
+
+impl Bindings {
+    fn get(&self, name: &str) -> Option<Arc<dyn Binding>> {
+        self.0
+            .iter()
+            .find(|binding| binding.name() == name)
+            .cloned()
+    }
+}
+
+deno_core::extension!(bindings, ops = [op_binding_call]);
+
+#[op2(async)]
+#[serde]
+async fn op_binding_call(
+    state: Rc<RefCell<OpState>>,
+    #[string] name: String,
+    #[string] method: String,
+    #[serde] args: Vec<serde_json::Value>,
+) -> Result<serde_json::Value, AnyError> {
+    debug!("op_binding_call {name}.{method}");
+
+    let binding = state
+        .borrow()
+        .try_borrow::<Bindings>()
+        .and_then(|bindings| bindings.get(&name));
+
+    match binding {
+        Some(binding) => binding.call(&method, args).await,
+        None => Err(type_error(format!("Binding {name} not configured"))),
+    }
+}
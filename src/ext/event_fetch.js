@@ -1,6 +1,8 @@
-import { core } from "ext:core/mod.js";
-import { newSignal } from "ext:deno_web/03_abort_signal.js";
+import { core, primordials } from "ext:core/mod.js";
+import { newSignal, signalAbort } from "ext:deno_web/03_abort_signal.js";
+import { DOMException } from "ext:deno_web/01_dom_exception.js";
 import {
+  Headers,
   guardFromHeaders,
   headersFromHeaderList,
 } from "ext:deno_fetch/20_headers.js";
@@ -10,8 +12,30 @@ import {
   fromInnerRequest,
 } from "ext:deno_fetch/23_request.js";
 import { toInnerResponse, Response } from "ext:deno_fetch/23_response.js";
+import {
+  ReadableStreamPrototype,
+  getReadableStreamResourceBacking,
+} from "ext:deno_web/06_streams.js";
 
-import { op_fetch_init, op_fetch_respond } from "ext:core/ops";
+import {
+  op_fetch_init,
+  op_fetch_early_hints,
+  op_fetch_pass_through,
+  op_fetch_wait_abort,
+  op_fetch_respond,
+  op_fetch_respond_file,
+  op_fetch_respond_stream_start,
+  op_fetch_respond_stream_chunk,
+  op_fetch_respond_stream_resource,
+  op_fetch_respond_stream_trailers,
+  op_fetch_respond_stream_end,
+} from "ext:core/ops";
+
+const {
+  ObjectDefineProperties,
+  ObjectPrototypeIsPrototypeOf,
+  TypedArrayPrototypeGetSymbolToStringTag,
+} = primordials;
 
 let fetchEventListener;
 
@@ -23,6 +47,83 @@ function registerFetchEventListener(listener) {
   fetchEventListener = listener;
 }
 
+function unregisterFetchEventListener() {
+  fetchEventListener = undefined;
+}
+
+// Body is backed by a ReadableStream (e.g. an upstream fetch or a user stream)
+function isStreamBody(inner) {
+  return (
+    inner.body !== null &&
+    ObjectPrototypeIsPrototypeOf(
+      ReadableStreamPrototype,
+      inner.body.streamOrStatic
+    )
+  );
+}
+
+// Forward chunks as they are read, without buffering the whole body
+async function respondWithStream(rid, inner, trailers) {
+  const streamRid = op_fetch_respond_stream_start(rid, {
+    status: inner.status,
+    headerList: inner.headerList,
+  });
+
+  try {
+    if (inner.body !== null) {
+      await pipeBody(streamRid, inner.body.stream);
+    }
+
+    // Trailers may be a promise resolved once the body has been produced
+    if (trailers !== undefined) {
+      const headers = new Headers(await trailers);
+      op_fetch_respond_stream_trailers(streamRid, [...headers]);
+    }
+  } finally {
+    op_fetch_respond_stream_end(streamRid);
+  }
+}
+
+async function pipeBody(streamRid, stream) {
+  const resourceBacking = getReadableStreamResourceBacking(stream);
+  const reader = stream.getReader();
+
+  try {
+    // Upstream bodies are read by the host straight from their resource
+    if (resourceBacking !== undefined) {
+      try {
+        await op_fetch_respond_stream_resource(streamRid, resourceBacking.rid);
+      } finally {
+        if (resourceBacking.autoClose) {
+          core.tryClose(resourceBacking.rid);
+        }
+      }
+
+      return;
+    }
+
+    while (true) {
+      const { value, done } = await reader.read();
+
+      if (done) {
+        break;
+      }
+
+      if (TypedArrayPrototypeGetSymbolToStringTag(value) !== "Uint8Array") {
+        throw new TypeError("Response body chunks must be Uint8Array");
+      }
+
+      await op_fetch_respond_stream_chunk(streamRid, value);
+    }
+  } finally {
+    reader.releaseLock();
+  }
+}
+
+function hasFetchEventListener() {
+  return fetchEventListener !== undefined;
+}
+
 function triggerFetchEvent(rid) {
   if (!fetchEventListener) {
     throw new Error("No fetch event listener registered");
@@ -32,6 +133,18 @@ function triggerFetchEvent(rid) {
 
   const signal = newSignal();
 
+  // Host abort (e.g. client disconnected), must not keep the event loop alive
+  if (evt.abortRid !== null) {
+    const aborted = op_fetch_wait_abort(evt.abortRid);
+    core.unrefOpPromise(aborted);
+
+    aborted.then(() =>
+      signal[signalAbort](
+        new DOMException("The request was aborted", "AbortError")
+      )
+    );
+  }
+
   const { body } = extractBody(evt.req.body);
 
   const inner = newInnerRequest(
@@ -43,24 +156,80 @@ function triggerFetchEvent(rid) {
 
   const guard = guardFromHeaders(headersFromHeaderList(inner.headerList));
 
-  fetchEventListener({
-    request: fromInnerRequest(inner, signal, guard),
-    respondWith: async (resOrPromise) => {
-      let response = core.isPromise(resOrPromise)
-        ? await resOrPromise
-        : resOrPromise;
-
-      if (!(response instanceof Response)) {
-        throw new TypeError("Response must be a Response object");
-      }
+  const request = fromInnerRequest(inner, signal, guard);
+
+  // Path and query, already split by the host to spare url parsing for routing,
+  // cf holds the host metadata of the request if any
+  ObjectDefineProperties(request, {
+    path: { value: evt.req.path, enumerable: true },
+    query: { value: evt.req.query, enumerable: true },
+    cf: { value: evt.metadata ?? undefined, enumerable: true },
+  });
+
+  // respondWith and respondWithFile may only be called once, the second call
+  // throws synchronously like FetchEvent.respondWith in browsers
+  let responded = false;
 
-      const inner = toInnerResponse(response);
+  const markResponded = () => {
+    if (responded) {
+      throw new TypeError("Response already sent");
+    }
 
-      const body = await response.arrayBuffer();
+    responded = true;
+  };
 
-      op_fetch_respond(evt.rid, { ...inner, body });
+  const respond = async (resOrPromise, trailers) => {
+    let response = core.isPromise(resOrPromise)
+      ? await resOrPromise
+      : resOrPromise;
+
+    if (!(response instanceof Response)) {
+      throw new TypeError("Response must be a Response object");
+    }
+
+    const inner = toInnerResponse(response);
+
+    if (isStreamBody(inner) || trailers !== undefined) {
+      return respondWithStream(evt.rid, inner, trailers);
+    }
+
+    const body = inner.body === null ? null : await response.arrayBuffer();
+
+    op_fetch_respond(evt.rid, { ...inner, body });
+  };
+
+  fetchEventListener({
+    request,
+    // Send a 103 Early Hints interim response, may be called several times
+    earlyHints: (headers) => {
+      op_fetch_early_hints(evt.rid, [...new Headers(headers)]);
+    },
+    // Let the host forward the request to the origin if the worker fails before responding
+    passThroughOnException: () => {
+      op_fetch_pass_through(evt.rid);
+    },
+    // Trailers (HeadersInit or a promise of it) are sent after the body
+    respondWith: (resOrPromise, { trailers } = {}) => {
+      markResponded();
+
+      return respond(resOrPromise, trailers);
+    },
+    // Serve a file from the host file response root, never read by the worker
+    respondWithFile: (path, { status = 200, headers } = {}) => {
+      markResponded();
+
+      op_fetch_respond_file(
+        evt.rid,
+        { status, headerList: [...new Headers(headers)], body: null },
+        path
+      );
     },
   });
 }
 
-export { triggerFetchEvent, registerFetchEventListener };
+export {
+  triggerFetchEvent,
+  hasFetchEventListener,
+  registerFetchEventListener,
+  unregisterFetchEventListener,
+};
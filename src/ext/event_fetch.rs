@@ -1,19 +1,210 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use bytes::Bytes;
+use deno_core::error::type_error;
 use deno_core::error::AnyError;
 use deno_core::op2;
 use deno_core::serde::Deserialize;
 use deno_core::serde::Serialize;
+use deno_core::serde_v8;
+use deno_core::v8;
 use deno_core::Extension;
 use deno_core::ExtensionFileSource;
+use deno_core::JsBuffer;
 use deno_core::OpState;
 use deno_core::ResourceId;
 use log::debug;
+use tokio::io::AsyncReadExt;
+
+use crate::task::ErrorSender;
+use crate::util::take_resource;
+use crate::RuntimeLimits;
 
 type HttpRequest = http_v02::Request<Bytes>;
-type HttpResponse = http_v02::Response<Bytes>;
+type HttpResponse = http_v02::Response<ResponseBody>;
 type ResponseSender = tokio::sync::oneshot::Sender<HttpResponse>;
+type Trailers = Vec<(String, String)>;
+type EarlyHintsSender = tokio::sync::mpsc::UnboundedSender<http_v02::Response<()>>;
+type AbortReceiver = tokio::sync::oneshot::Receiver<()>;
+
+/// Number of chunks buffered between the worker and the host
+/// before a streamed response applies backpressure to js realm.
+const RESPONSE_STREAM_CAPACITY: usize = 16;
+
+/// Size of the reads when piping a resource into a response stream.
+const RESOURCE_READ_SIZE: usize = 64 * 1024;
+
+/// ResponseBody is the body of the response sent to the host.
+///
+/// Static bodies (strings, buffers) are sent in one piece, while
+/// bodies backed by a ReadableStream (e.g. an upstream fetch) are
+/// forwarded chunk by chunk as the worker produces them.
+#[derive(Debug)]
+pub enum ResponseBody {
+    /// No body at all: statuses without content (1xx, 204, 205, 304) and
+    /// responses to `HEAD` requests. The host must not add a `Content-Length`.
+    None,
+    /// Empty body of a status that has content, sent with `Content-Length: 0`.
+    Empty,
+    Bytes(Bytes),
+    Stream(ResponseStream),
+    /// File under `WorkerOptions::file_response_root`, to be streamed by the host.
+    File(PathBuf),
+}
+
+impl ResponseBody {
+    /// Whether a response has a body, given its status and whether it answers a `HEAD` request.
+    fn allowed(status: u16, head: bool) -> bool {
+        !head && !matches!(status, 100..=199 | 204 | 205 | 304)
+    }
+
+    /// Body of a buffered response, given its status and whether it answers a `HEAD` request.
+    pub(crate) fn buffered(status: u16, head: bool, body: Option<Bytes>) -> Self {
+        if !Self::allowed(status, head) {
+            return ResponseBody::None;
+        }
+
+        match body {
+            Some(body) if !body.is_empty() => ResponseBody::Bytes(body),
+            _ => ResponseBody::Empty,
+        }
+    }
+}
+
+/// ResponseStream yields the chunks of a streamed response body,
+/// followed by the trailers the worker may send once the body is complete.
+#[derive(Debug)]
+pub struct ResponseStream {
+    chunks: tokio::sync::mpsc::Receiver<Bytes>,
+    trailers: tokio::sync::oneshot::Receiver<Trailers>,
+}
+
+impl ResponseStream {
+    /// Receive the next chunk, `None` once the body is complete.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        self.chunks.recv().await
+    }
+
+    /// Trailers sent by the worker, empty if it didn't send any.
+    ///
+    /// Should be called once `recv` returned `None`.
+    pub async fn trailers(self) -> Trailers {
+        self.trailers.await.unwrap_or_default()
+    }
+}
+
+/// FileStream reads the file of a `ResponseBody::File` in chunks, as the
+/// host sends them, without loading the whole file in memory.
+#[derive(Debug)]
+pub struct FileStream {
+    file: tokio::fs::File,
+}
+
+/// Size of the chunks read by `FileStream`.
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+impl FileStream {
+    pub async fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = tokio::fs::File::open(path).await?;
+
+        Ok(FileStream { file })
+    }
+
+    /// Read the next chunk, `None` once the whole file was read.
+    pub async fn recv(&mut self) -> Option<std::io::Result<Bytes>> {
+        let mut buf = vec![0; FILE_CHUNK_SIZE];
+
+        match self.file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some(Ok(Bytes::from(buf)))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Directory a worker may serve files from with `respondWithFile`.
+#[derive(Debug, Clone)]
+pub(crate) struct FileResponseRoot(pub(crate) PathBuf);
+
+impl FileResponseRoot {
+    /// Resolve a path relative to the root, refusing anything outside of it.
+    fn resolve(&self, path: &str) -> Result<PathBuf, AnyError> {
+        let root = self.0.canonicalize()?;
+        let file = root.join(path.trim_start_matches('/')).canonicalize()?;
+
+        if !file.starts_with(&root) || !file.is_file() {
+            return Err(type_error(format!("Cannot respond with file {path}")));
+        }
+
+        Ok(file)
+    }
+}
+
+/// What to do when a worker responds with a text body that isn't valid UTF-8.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidUtf8Policy {
+    /// Send the body as is.
+    #[default]
+    Allow,
+    /// Send the body as is, but log a warning.
+    Warn,
+    /// Refuse the response, `respondWith` rejects in js realm.
+    Reject,
+}
+
+/// What to do when the body of a response doesn't match its declared `Content-Length`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ContentLengthPolicy {
+    /// Send the response as is.
+    #[default]
+    Allow,
+    /// Send the response as is, but log a warning.
+    Warn,
+    /// Fail the response. Buffered responses are refused, `respondWith` rejects
+    /// in js realm. The head of a streamed response is already sent when the
+    /// mismatch is found at the end of the body: the error is raised in js realm.
+    Reject,
+}
+
+impl ContentLengthPolicy {
+    fn check(self, declared: &str, len: u64) -> Result<(), AnyError> {
+        if declared.trim().parse::<u64>() == Ok(len) {
+            return Ok(());
+        }
+
+        let message = format!("Content-Length mismatch: declared {declared}, body is {len} bytes");
+
+        match self {
+            ContentLengthPolicy::Allow => Ok(()),
+            ContentLengthPolicy::Warn => {
+                log::warn!("{message}");
+                Ok(())
+            }
+            ContentLengthPolicy::Reject => Err(type_error(message)),
+        }
+    }
+}
+
+/// Declared `Content-Length` of a response, unless its status never has a body.
+fn declared_content_length(status: u16, headers: &[(String, String)]) -> Option<&str> {
+    if matches!(status, 100..=199 | 204 | 304) {
+        return None;
+    }
+
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .map(|(_, v)| v.as_str())
+}
 
 /// FetchResponse is a struct that represents the response
 /// from a fetch request that comes from js realm.
@@ -27,18 +218,117 @@ pub struct FetchResponse {
     body: Option<Bytes>,
 }
 
-impl Into<HttpResponse> for FetchResponse {
-    fn into(self) -> HttpResponse {
+impl FetchResponse {
+    fn into_response(self, body: ResponseBody) -> Result<HttpResponse, AnyError> {
         let mut builder = http_v02::Response::builder().status(self.status);
 
         for (k, v) in self.headers {
             builder = builder.header(k, v);
         }
 
-        match self.body {
-            Some(body) => builder.body(body).unwrap(),
-            None => builder.body(Default::default()).unwrap(),
+        builder
+            .body(body)
+            .map_err(|err| type_error(format!("Invalid response: {err}")))
+    }
+
+    /// Check the status of a response not built by the JS `Response` constructor.
+    fn check_status(&self) -> Result<(), AnyError> {
+        if !(200..=599).contains(&self.status) {
+            return Err(type_error(format!(
+                "The status provided ({}) is outside the range [200, 599]",
+                self.status
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl FetchResponse {
+    /// Whether the content type announces a text body.
+    fn is_text(&self) -> bool {
+        self.headers.iter().any(|(k, v)| {
+            let v = v.to_ascii_lowercase();
+
+            k.eq_ignore_ascii_case("content-type")
+                && (v.starts_with("text/") || v.contains("charset=utf-8"))
+        })
+    }
+
+    fn check_utf8(&self, policy: InvalidUtf8Policy) -> Result<(), AnyError> {
+        let body = match &self.body {
+            Some(body) if policy != InvalidUtf8Policy::Allow && self.is_text() => body,
+            _ => return Ok(()),
+        };
+
+        if let Err(err) = std::str::from_utf8(body) {
+            match policy {
+                InvalidUtf8Policy::Allow => {}
+                InvalidUtf8Policy::Warn => {
+                    log::warn!("text response body is not valid UTF-8: {err}")
+                }
+                InvalidUtf8Policy::Reject => {
+                    return Err(deno_core::error::type_error(format!(
+                        "Text response body is not valid UTF-8: {err}"
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FetchResponse {
+    /// Check the declared `Content-Length` against the body that will be sent.
+    ///
+    /// Responses without a body (e.g. to a `HEAD` request) keep the length
+    /// declared by the worker. Framing is left to the host, a missing length
+    /// isn't added.
+    fn check_content_length(
+        &self,
+        policy: ContentLengthPolicy,
+        body: &ResponseBody,
+    ) -> Result<(), AnyError> {
+        let len = match body {
+            ResponseBody::Bytes(body) => body.len() as u64,
+            ResponseBody::Empty => 0,
+            _ => return Ok(()),
+        };
+
+        match declared_content_length(self.status, &self.headers) {
+            Some(declared) => policy.check(declared, len),
+            None => Ok(()),
+        }
+    }
+
+    /// Enforce the response header limits of the worker, if any.
+    fn check_headers(&self, state: &OpState) -> Result<(), AnyError> {
+        let limits = match state.try_borrow::<RuntimeLimits>() {
+            Some(limits) => limits,
+            None => return Ok(()),
+        };
+
+        if let Some(max) = limits.max_response_headers {
+            if self.headers.len() > max {
+                return Err(type_error(format!(
+                    "Too many headers in response: {} (max {max})",
+                    self.headers.len()
+                )));
+            }
         }
+
+        if let Some(max) = limits.max_header_bytes {
+            let size: usize = self.headers.iter().map(|(k, v)| k.len() + v.len()).sum();
+
+            if size > max {
+                return Err(type_error(format!(
+                    "Response headers too large: {size} bytes (max {max})"
+                )));
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -46,6 +336,10 @@ impl Into<HttpResponse> for FetchResponse {
 pub struct FetchInit {
     pub(crate) req: HttpRequest,
     pub(crate) res_tx: ResponseSender,
+    pub(crate) early_hints_tx: Option<EarlyHintsSender>,
+    pub(crate) abort_rx: Option<AbortReceiver>,
+    pub(crate) error_tx: Option<ErrorSender>,
+    pub(crate) metadata: Option<HashMap<String, deno_core::serde_json::Value>>,
 }
 
 impl FetchInit {
@@ -53,8 +347,54 @@ impl FetchInit {
         FetchInit {
             req,
             res_tx,
+            early_hints_tx: None,
+            abort_rx: None,
+            error_tx: None,
+            metadata: None,
         }
     }
+
+    /// Receive the reason of the failure if the task fails.
+    pub fn with_error_tx(mut self, error_tx: ErrorSender) -> Self {
+        self.error_tx = Some(error_tx);
+        self
+    }
+
+    /// Path of the request.
+    pub(crate) fn path(&self) -> &str {
+        self.req.uri().path()
+    }
+
+    /// Answer the request from the host, without dispatching it to the worker.
+    pub(crate) fn respond(self, res: HttpResponse) {
+        if self.res_tx.send(res).is_err() {
+            debug!("response receiver dropped");
+        }
+    }
+
+    /// Abort `event.request.signal` in the worker when the host sends on
+    /// (or drops) the matching sender, e.g. once the client disconnected.
+    pub fn with_abort(mut self, abort_rx: AbortReceiver) -> Self {
+        self.abort_rx = Some(abort_rx);
+        self
+    }
+
+    /// Metadata of the request set by the host (e.g. client IP, country, TLS version),
+    /// opaque to the runtime and exposed to the worker as `event.request.cf`.
+    pub fn with_metadata(
+        mut self,
+        metadata: HashMap<String, deno_core::serde_json::Value>,
+    ) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Receive `103 Early Hints` interim responses sent by the worker
+    /// before the final response. The worker may send several of them.
+    pub fn with_early_hints(mut self, early_hints_tx: EarlyHintsSender) -> Self {
+        self.early_hints_tx = Some(early_hints_tx);
+        self
+    }
 }
 
 impl deno_core::Resource for FetchInit {
@@ -64,7 +404,12 @@ impl deno_core::Resource for FetchInit {
 }
 
 #[derive(Debug)]
-struct FetchTx(ResponseSender);
+struct FetchTx {
+    res_tx: ResponseSender,
+    early_hints_tx: Option<EarlyHintsSender>,
+    /// Whether the request is a `HEAD`, answered without body.
+    head: bool,
+}
 
 impl deno_core::Resource for FetchTx {
     fn close(self: Rc<Self>) {
@@ -72,16 +417,156 @@ impl deno_core::Resource for FetchTx {
     }
 }
 
+/// Response of the current fetch task, for metrics.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ResponseStats {
+    pub(crate) status: u16,
+    pub(crate) bytes_in: u64,
+    pub(crate) bytes_out: u64,
+    pub(crate) started: bool,
+    /// Time spent converting the request into a JS `FetchEvent`.
+    pub(crate) init_time: std::time::Duration,
+    /// Response sender of the task, until the worker responds.
+    pub(crate) tx_rid: Option<ResourceId>,
+}
+
+/// Whether the current fetch task called `event.passThroughOnException()`.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct PassThrough(pub(crate) bool);
+
+fn record_response(state: &mut OpState, status: u16, bytes: u64) {
+    if let Some(stats) = state.try_borrow_mut::<ResponseStats>() {
+        stats.status = status;
+        stats.bytes_out = bytes;
+        stats.started = true;
+    }
+}
+
+fn record_response_chunk(state: &RefCell<OpState>, bytes: usize) {
+    if let Some(stats) = state.borrow_mut().try_borrow_mut::<ResponseStats>() {
+        stats.bytes_out += bytes as u64;
+    }
+}
+
+#[derive(Debug)]
+struct FetchAbort {
+    abort_rx: RefCell<Option<AbortReceiver>>,
+}
+
+impl deno_core::Resource for FetchAbort {}
+
+/// Release the response sender of a fetch task once it completed or failed.
+///
+/// If the worker didn't respond, `res` is sent on its behalf, or the sender
+/// is dropped if `None`. Returns whether a response was sent.
+pub(crate) fn respond_default(state: &mut OpState, res: Option<HttpResponse>) -> bool {
+    let rid = match state.try_borrow_mut::<ResponseStats>() {
+        Some(stats) if !stats.started => stats.tx_rid.take(),
+        _ => None,
+    };
+
+    let tx = match rid.map(|rid| take_fetch_tx(state, rid)) {
+        Some(Ok(tx)) => tx,
+        _ => return false,
+    };
+
+    let res = match res {
+        Some(res) => res,
+        None => return false,
+    };
+
+    let bytes = match res.body() {
+        ResponseBody::Bytes(body) => body.len() as u64,
+        _ => 0,
+    };
+
+    record_response(state, res.status().as_u16(), bytes);
+
+    if tx.send(res).is_err() {
+        debug!("default response receiver dropped");
+    }
+
+    true
+}
+
+/// Take the response sender of a fetch event, a response can only be sent once.
+fn take_fetch_tx(state: &mut OpState, rid: ResourceId) -> Result<FetchTx, AnyError> {
+    if !state.resource_table.has(rid) {
+        return Err(type_error("Response already sent"));
+    }
+
+    take_resource::<FetchTx>(state, rid)
+}
+
+/// Whether the fetch event answers a `HEAD` request, fails if a response was already sent.
+fn fetch_tx_head(state: &OpState, rid: ResourceId) -> Result<bool, AnyError> {
+    match state.resource_table.get::<FetchTx>(rid) {
+        Ok(tx) => Ok(tx.head),
+        Err(_) => Err(type_error("Response already sent")),
+    }
+}
+
 impl FetchTx {
-    pub fn send(self, res: FetchResponse) -> Result<(), HttpResponse> {
-        self.0.send(res.into())
+    #[allow(clippy::result_large_err)]
+    pub fn send(self, res: HttpResponse) -> Result<(), HttpResponse> {
+        self.res_tx.send(res)
+    }
+}
+
+#[derive(Debug)]
+struct FetchStreamTx {
+    /// Sender of the body chunks, `None` if the response has no body (e.g. to a
+    /// `HEAD` request): the chunks the worker still produces are discarded.
+    chunks: Option<tokio::sync::mpsc::Sender<Bytes>>,
+    trailers: RefCell<Option<tokio::sync::oneshot::Sender<Trailers>>>,
+    /// Declared `Content-Length`, checked once the body is complete.
+    content_length: Option<(String, ContentLengthPolicy)>,
+    /// Bytes sent so far.
+    sent: Cell<u64>,
+}
+
+impl FetchStreamTx {
+    async fn send(&self, chunk: Bytes) -> Result<(), AnyError> {
+        let len = chunk.len() as u64;
+
+        let chunks = match &self.chunks {
+            Some(chunks) => chunks,
+            None => return Ok(()),
+        };
+
+        match chunks.send(chunk).await {
+            Ok(()) => {
+                self.sent.set(self.sent.get() + len);
+                Ok(())
+            }
+            Err(_) => Err(type_error("response stream closed")),
+        }
+    }
+}
+
+impl deno_core::Resource for FetchStreamTx {
+    fn name(&self) -> Cow<'_, str> {
+        "fetchStreamTx".into()
     }
 }
 
+/// Number of streamed responses not ended yet.
+pub(crate) fn open_streams(state: &OpState) -> usize {
+    state
+        .resource_table
+        .names()
+        .filter(|(_, name)| name == "fetchStreamTx")
+        .count()
+}
+
 #[derive(Debug, Serialize)]
 struct InnerRequest {
     method: String,
     url: String,
+    /// Percent-encoded path of the url.
+    path: String,
+    /// Raw query string of the url, without the leading `?`.
+    query: String,
     headers: Vec<(String, String)>,
     body: Option<Bytes>,
 }
@@ -90,30 +575,86 @@ struct InnerRequest {
 struct FetchEvent {
     req: InnerRequest,
     rid: u32,
+    #[serde(rename = "abortRid")]
+    abort_rid: Option<u32>,
+    metadata: Option<HashMap<String, deno_core::serde_json::Value>>,
+}
+
+/// Methods normalized to uppercase by the fetch spec, whatever their case.
+const NORMALIZED_METHODS: [&str; 6] = ["DELETE", "GET", "HEAD", "OPTIONS", "POST", "PUT"];
+
+/// Methods a JS `Request` can't be created with.
+const FORBIDDEN_METHODS: [&str; 3] = ["CONNECT", "TRACE", "TRACK"];
+
+/// Method of a request as the worker `Request` will see it.
+///
+/// `http::Method` only holds valid tokens, but keeps the case of extension
+/// methods: `get` is uppercased like JS `Request` does, methods the worker
+/// can't be given are refused here rather than failing the fetch event.
+fn normalize_method(method: &http_v02::Method) -> Result<String, AnyError> {
+    let method = method.as_str();
+
+    if let Some(forbidden) = FORBIDDEN_METHODS
+        .iter()
+        .find(|forbidden| forbidden.eq_ignore_ascii_case(method))
+    {
+        return Err(type_error(format!(
+            "Unsupported request method {forbidden}"
+        )));
+    }
+
+    match NORMALIZED_METHODS
+        .iter()
+        .find(|normalized| normalized.eq_ignore_ascii_case(method))
+    {
+        Some(normalized) => Ok(normalized.to_string()),
+        None => Ok(method.to_string()),
+    }
 }
 
-impl From<HttpRequest> for InnerRequest {
-    fn from(req: HttpRequest) -> Self {
-        InnerRequest {
-            method: req.method().to_string(),
+impl TryFrom<HttpRequest> for InnerRequest {
+    type Error = AnyError;
+
+    fn try_from(req: HttpRequest) -> Result<Self, AnyError> {
+        Ok(InnerRequest {
+            method: normalize_method(req.method())?,
             url: req.uri().to_string(),
+            path: req.uri().path().to_string(),
+            query: req.uri().query().unwrap_or_default().to_string(),
             headers: req
                 .headers()
                 .iter()
-                .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_string()))
+                .map(|(k, v)| {
+                    (
+                        k.to_string(),
+                        String::from_utf8_lossy(v.as_bytes()).into_owned(),
+                    )
+                })
                 .collect(),
             body: match req.body().len() {
                 0 => None,
-                _ => Some(req.body().to_owned())
-            }
-        }
+                _ => Some(req.body().to_owned()),
+            },
+        })
     }
 }
 
 deno_core::extension!(
     fetch_event,
     deps = [deno_console, deno_fetch],
-    ops = [op_fetch_init, op_fetch_respond],
+    ops = [
+        op_fetch_init,
+        op_fetch_early_hints,
+        op_fetch_pass_through,
+        op_fetch_wait_abort,
+        op_fetch_respond,
+        op_fetch_respond_file,
+        op_fetch_respond_stream_start,
+        op_fetch_respond_stream_chunk,
+        op_fetch_respond_stream_resource,
+        op_fetch_respond_stream_trailers,
+        op_fetch_respond_stream_end
+    ],
     customizer = |ext: &mut Extension| {
         ext.esm_files.to_mut().push(ExtensionFileSource::new(
             "ext:event_fetch.js",
@@ -123,20 +664,118 @@ deno_core::extension!(
     }
 );
 
+/// Serialization of the request is done in the op (rather than by `#[serde]`)
+/// so its duration is included in the task metrics.
 #[op2]
-#[serde]
-fn op_fetch_init(state: &mut OpState, #[smi] rid: ResourceId) -> Result<FetchEvent, AnyError> {
+fn op_fetch_init<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+) -> Result<v8::Local<'s, v8::Value>, AnyError> {
     debug!("op_fetch_init {rid}");
 
-    let evt = state.resource_table.take::<FetchInit>(rid).unwrap();
+    let start = std::time::Instant::now();
+
+    let evt = take_resource::<FetchInit>(state, rid)?;
 
-    let evt = Rc::try_unwrap(evt).unwrap();
+    let head = evt.req.method() == http_v02::Method::HEAD;
+    let req = InnerRequest::try_from(evt.req)?;
+    let bytes_in = req.body.as_ref().map_or(0, |body| body.len() as u64);
 
-    let req = InnerRequest::from(evt.req);
+    let rid = state.resource_table.add(FetchTx {
+        res_tx: evt.res_tx,
+        early_hints_tx: evt.early_hints_tx,
+        head,
+    });
 
-    let rid = state.resource_table.add(FetchTx(evt.res_tx)); 
+    let abort_rid = evt.abort_rx.map(|abort_rx| {
+        state.resource_table.add(FetchAbort {
+            abort_rx: RefCell::new(Some(abort_rx)),
+        })
+    });
+
+    let evt = serde_v8::to_v8(
+        scope,
+        FetchEvent {
+            req,
+            rid,
+            abort_rid,
+            metadata: evt.metadata,
+        },
+    )?;
 
-    Ok(FetchEvent { req, rid })
+    if let Some(stats) = state.try_borrow_mut::<ResponseStats>() {
+        stats.init_time = start.elapsed();
+        stats.bytes_in = bytes_in;
+        stats.tx_rid = Some(rid);
+    }
+
+    Ok(evt)
+}
+
+/// Resolves once the host aborted the request.
+#[op2(async)]
+async fn op_fetch_wait_abort(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+) -> Result<(), AnyError> {
+    let abort = state.borrow_mut().resource_table.take::<FetchAbort>(rid)?;
+
+    let abort_rx = abort.abort_rx.borrow_mut().take();
+
+    if let Some(abort_rx) = abort_rx {
+        // Sent or dropped, both mean the host is no longer interested
+        let _ = abort_rx.await;
+    }
+
+    Ok(())
+}
+
+#[op2(fast)]
+fn op_fetch_pass_through(state: &mut OpState, #[smi] rid: ResourceId) -> Result<(), AnyError> {
+    debug!("op_fetch_pass_through {rid}");
+
+    if !state.resource_table.has(rid) {
+        return Err(type_error("Response already sent"));
+    }
+
+    state.put::<PassThrough>(PassThrough(true));
+
+    Ok(())
+}
+
+#[op2]
+fn op_fetch_early_hints(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+    #[serde] headers: Vec<(String, String)>,
+) -> Result<(), AnyError> {
+    debug!("op_fetch_early_hints {:?}", headers);
+
+    let tx = match state.resource_table.get::<FetchTx>(rid) {
+        Ok(tx) => tx,
+        Err(_) => return Err(deno_core::error::type_error("Response already sent")),
+    };
+
+    let early_hints_tx = match &tx.early_hints_tx {
+        Some(early_hints_tx) => early_hints_tx,
+        None => {
+            debug!("early hints not supported by the host, ignoring");
+            return Ok(());
+        }
+    };
+
+    let mut builder = http_v02::Response::builder().status(103);
+
+    for (k, v) in headers {
+        builder = builder.header(k, v);
+    }
+
+    if early_hints_tx.send(builder.body(())?).is_err() {
+        debug!("early hints receiver dropped");
+    }
+
+    Ok(())
 }
 
 #[op2]
@@ -144,19 +783,216 @@ fn op_fetch_init(state: &mut OpState, #[smi] rid: ResourceId) -> Result<FetchEve
 fn op_fetch_respond(
     state: &mut OpState,
     #[smi] rid: ResourceId,
-    #[serde] res: FetchResponse,
+    #[serde] mut res: FetchResponse,
 ) -> Result<(), AnyError> {
     debug!("op_fetch_respond with status {}", res.status);
 
-    let tx = match state.resource_table.take::<FetchTx>(rid) {
-        Ok(tx) => tx,
-        Err(err) => return Err(err),
-    };
+    let policy = state
+        .try_borrow::<InvalidUtf8Policy>()
+        .copied()
+        .unwrap_or_default();
+
+    // Before the response checks, a second response reports why it failed
+    let head = fetch_tx_head(state, rid)?;
+
+    res.check_headers(state)?;
+    res.check_utf8(policy)?;
+
+    let bytes = res.body.as_ref().map_or(0, |body| body.len() as u64);
+    let body = ResponseBody::buffered(res.status, head, res.body.take());
+
+    let policy = state
+        .try_borrow::<ContentLengthPolicy>()
+        .copied()
+        .unwrap_or_default();
+
+    res.check_content_length(policy, &body)?;
 
-    let tx = Rc::try_unwrap(tx).unwrap();
+    let status = res.status;
+    let res = res.into_response(body)?;
+    let tx = take_fetch_tx(state, rid)?;
+
+    record_response(state, status, bytes);
 
     let tx = tx.send(res);
     debug!("op_fetch_respond tx {:?}", tx);
 
     Ok(())
 }
+
+/// Respond with a file the host streams without going through the isolate,
+/// only available if the host configured a file response root.
+#[op2]
+fn op_fetch_respond_file(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+    #[serde] res: FetchResponse,
+    #[string] path: &str,
+) -> Result<(), AnyError> {
+    debug!("op_fetch_respond_file {path}");
+
+    fetch_tx_head(state, rid)?;
+
+    res.check_status()?;
+    res.check_headers(state)?;
+
+    let file = match state.try_borrow::<FileResponseRoot>() {
+        Some(root) => root.resolve(path)?,
+        None => return Err(type_error("File responses are not enabled")),
+    };
+
+    let bytes = std::fs::metadata(&file).map_or(0, |metadata| metadata.len());
+
+    let status = res.status;
+    let res = res.into_response(ResponseBody::File(file))?;
+    let tx = take_fetch_tx(state, rid)?;
+
+    record_response(state, status, bytes);
+
+    let tx = tx.send(res);
+    debug!("op_fetch_respond_file tx {:?}", tx);
+
+    Ok(())
+}
+
+#[op2]
+#[smi]
+fn op_fetch_respond_stream_start(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+    #[serde] res: FetchResponse,
+) -> Result<ResourceId, AnyError> {
+    debug!("op_fetch_respond_stream_start with status {}", res.status);
+
+    let head = fetch_tx_head(state, rid)?;
+
+    res.check_headers(state)?;
+
+    let (trailers_tx, trailers_rx) = tokio::sync::oneshot::channel();
+
+    // Without a body, trailers are dropped along with their receiver
+    let (chunks_tx, body) = match ResponseBody::allowed(res.status, head) {
+        true => {
+            let (chunks_tx, chunks_rx) = tokio::sync::mpsc::channel(RESPONSE_STREAM_CAPACITY);
+
+            let stream = ResponseStream {
+                chunks: chunks_rx,
+                trailers: trailers_rx,
+            };
+
+            (Some(chunks_tx), ResponseBody::Stream(stream))
+        }
+        false => (None, ResponseBody::None),
+    };
+
+    let policy = state
+        .try_borrow::<ContentLengthPolicy>()
+        .copied()
+        .unwrap_or_default();
+
+    // Responses without a body keep the declared length, like buffered ones
+    let content_length = match policy {
+        ContentLengthPolicy::Allow => None,
+        _ if chunks_tx.is_none() => None,
+        policy => declared_content_length(res.status, &res.headers)
+            .map(|declared| (declared.to_string(), policy)),
+    };
+
+    let status = res.status;
+    let res = res.into_response(body)?;
+    let tx = take_fetch_tx(state, rid)?;
+
+    record_response(state, status, 0);
+
+    let tx = tx.send(res);
+    debug!("op_fetch_respond_stream_start tx {:?}", tx);
+
+    Ok(state.resource_table.add(FetchStreamTx {
+        chunks: chunks_tx,
+        trailers: RefCell::new(Some(trailers_tx)),
+        content_length,
+        sent: Cell::new(0),
+    }))
+}
+
+#[op2(async)]
+async fn op_fetch_respond_stream_chunk(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+    #[buffer] chunk: JsBuffer,
+) -> Result<(), AnyError> {
+    let tx = state.borrow().resource_table.get::<FetchStreamTx>(rid)?;
+
+    if tx.chunks.is_some() {
+        record_response_chunk(&state, chunk.len());
+    }
+
+    tx.send(Bytes::from(chunk.to_vec())).await
+}
+
+/// Pipe a resource backed body (e.g. an upstream `fetch` response) into the
+/// response stream, chunks never enter the isolate heap.
+#[op2(async)]
+async fn op_fetch_respond_stream_resource(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+    #[smi] body_rid: ResourceId,
+) -> Result<(), AnyError> {
+    let tx = state.borrow().resource_table.get::<FetchStreamTx>(rid)?;
+
+    // Not worth reading a body that won't be sent
+    if tx.chunks.is_none() {
+        return Ok(());
+    }
+
+    let body = state.borrow().resource_table.get_any(body_rid)?;
+
+    loop {
+        let chunk = body.clone().read(RESOURCE_READ_SIZE).await?;
+
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        record_response_chunk(&state, chunk.len());
+
+        tx.send(Bytes::from(chunk)).await?;
+    }
+}
+
+#[op2]
+fn op_fetch_respond_stream_trailers(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+    #[serde] trailers: Trailers,
+) -> Result<(), AnyError> {
+    debug!("op_fetch_respond_stream_trailers {:?}", trailers);
+
+    let tx = state.resource_table.get::<FetchStreamTx>(rid)?;
+
+    let trailers_tx = match tx.trailers.borrow_mut().take() {
+        Some(trailers_tx) => trailers_tx,
+        None => return Err(deno_core::error::type_error("trailers already sent")),
+    };
+
+    // The host may not care about trailers
+    let _ = trailers_tx.send(trailers);
+
+    Ok(())
+}
+
+#[op2(fast)]
+fn op_fetch_respond_stream_end(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+) -> Result<(), AnyError> {
+    debug!("op_fetch_respond_stream_end");
+
+    // Dropping the sender closes the stream on the host side
+    let tx = state.resource_table.take::<FetchStreamTx>(rid)?;
+
+    match &tx.content_length {
+        Some((declared, policy)) => policy.check(declared, tx.sent.get()),
+        None => Ok(()),
+    }
+}
@@ -11,6 +11,14 @@ function registerScheduledEventListener(listener) {
   scheduledEventListener = listener;
 }
 
+function unregisterScheduledEventListener() {
+  scheduledEventListener = undefined;
+}
+
+function hasScheduledEventListener() {
+  return scheduledEventListener !== undefined;
+}
+
 function triggerScheduledEvent(rid) {
   if (!scheduledEventListener) {
     throw new Error("No scheduled event listener registered");
@@ -21,16 +29,27 @@ function triggerScheduledEvent(rid) {
   // Convert seconds to milliseconds
   const scheduledTime = evt.time;
 
+  // Set by `retry`, reported to the host once `waitUntil` settles
+  let outcome = null;
+
   scheduledEventListener({
     scheduledTime,
+    retry: ({ delaySeconds = 0 } = {}) => {
+      outcome = { retry: true, delaySecs: Math.max(0, Math.floor(delaySeconds)) };
+    },
     waitUntil: async (promise) => {
       if (core.isPromise(promise)) {
         await promise;
       }
 
-      op_scheduled_respond(evt.rid);
+      op_scheduled_respond(evt.rid, outcome);
     },
   });
 }
 
-export { triggerScheduledEvent, registerScheduledEventListener };
+export {
+  triggerScheduledEvent,
+  hasScheduledEventListener,
+  registerScheduledEventListener,
+  unregisterScheduledEventListener,
+};
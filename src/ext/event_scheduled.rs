@@ -1,7 +1,9 @@
+use std::collections::HashSet;
 use std::rc::Rc;
 
 use deno_core::error::AnyError;
 use deno_core::op2;
+use deno_core::serde::Deserialize;
 use deno_core::serde::Serialize;
 use deno_core::Extension;
 use deno_core::ExtensionFileSource;
@@ -9,12 +11,26 @@ use deno_core::OpState;
 use deno_core::ResourceId;
 use log::debug;
 
-type ResponseSender = tokio::sync::oneshot::Sender<()>;
+use crate::task::ErrorSender;
+use crate::util::take_resource;
+
+/// Outcome of a scheduled task, reported when `waitUntil` settles.
+///
+/// A task asks the host to run it again later with `event.retry({ delaySeconds })`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledOutcome {
+    pub retry: bool,
+    pub delay_secs: u64,
+}
+
+type ResponseSender = tokio::sync::oneshot::Sender<ScheduledOutcome>;
 
 #[derive(Debug)]
 pub struct ScheduledInit {
     pub(crate) res_tx: ResponseSender,
     pub(crate) time: u64,
+    pub(crate) error_tx: Option<ErrorSender>,
 }
 
 impl ScheduledInit {
@@ -22,8 +38,15 @@ impl ScheduledInit {
         ScheduledInit {
             res_tx,
             time,
+            error_tx: None,
         }
     }
+
+    /// Receive the reason of the failure if the task fails.
+    pub fn with_error_tx(mut self, error_tx: ErrorSender) -> Self {
+        self.error_tx = Some(error_tx);
+        self
+    }
 }
 
 impl deno_core::Resource for ScheduledInit {
@@ -32,6 +55,44 @@ impl deno_core::Resource for ScheduledInit {
     }
 }
 
+/// What to do when a scheduled task is dispatched while a previous one
+/// hasn't called `waitUntil` back yet (e.g. the host gave up awaiting `exec`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduledOverlapPolicy {
+    /// Run both tasks concurrently.
+    #[default]
+    Allow,
+    /// Run the event loop until the running tasks responded, then run the new task.
+    Queue,
+    /// Refuse the new task, `Worker::exec` returns an error.
+    Reject,
+}
+
+/// Scheduled tasks dispatched and not yet responded.
+#[derive(Debug, Default)]
+pub(crate) struct ScheduledRunning(HashSet<ResourceId>);
+
+/// Number of scheduled tasks the worker didn't respond to yet.
+pub(crate) fn scheduled_running(state: &OpState) -> usize {
+    state
+        .try_borrow::<ScheduledRunning>()
+        .map_or(0, |running| running.0.len())
+}
+
+/// Release a scheduled task once its `exec` completed or failed.
+///
+/// A task that didn't respond won't anymore: it stops counting as running
+/// and its response sender is dropped.
+pub(crate) fn release_scheduled(state: &mut OpState, rid: ResourceId) {
+    if let Some(running) = state.try_borrow_mut::<ScheduledRunning>() {
+        running.0.remove(&rid);
+    }
+
+    if state.resource_table.take::<ScheduledInit>(rid).is_ok() {
+        debug!("scheduled task {rid} completed without responding");
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct ScheduledEvent {
     rid: u32,
@@ -53,23 +114,41 @@ deno_core::extension!(
 
 #[op2]
 #[serde]
-fn op_scheduled_init(state: &mut OpState, #[smi] rid: ResourceId) -> Result<ScheduledEvent, AnyError> {
+fn op_scheduled_init(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+) -> Result<ScheduledEvent, AnyError> {
     debug!("op_scheduled_init {rid}");
 
-    let evt = state.resource_table.get::<ScheduledInit>(rid).unwrap();
+    let evt = state.resource_table.get::<ScheduledInit>(rid)?;
 
     let time = evt.time;
 
+    if let Some(running) = state.try_borrow_mut::<ScheduledRunning>() {
+        running.0.insert(rid);
+    }
+
     Ok(ScheduledEvent { rid, time })
 }
 
 #[op2]
 #[serde]
-fn op_scheduled_respond(state: &mut OpState, #[smi] rid: ResourceId) -> Result<(), AnyError> {
-    debug!("op_scheduled_respond");
+fn op_scheduled_respond(
+    state: &mut OpState,
+    #[smi] rid: ResourceId,
+    #[serde] outcome: Option<ScheduledOutcome>,
+) -> Result<(), AnyError> {
+    debug!("op_scheduled_respond {outcome:?}");
+
+    let evt = take_resource::<ScheduledInit>(state, rid)?;
 
-    match state.resource_table.take::<ScheduledInit>(rid) {
-        Ok(tx) => Ok(Rc::try_unwrap(tx).unwrap().res_tx.send(()).unwrap()),
-        Err(err) => Err(err),
+    if let Some(running) = state.try_borrow_mut::<ScheduledRunning>() {
+        running.0.remove(&rid);
     }
+
+    if evt.res_tx.send(outcome.unwrap_or_default()).is_err() {
+        debug!("scheduled response receiver dropped");
+    }
+
+    Ok(())
 }
@@ -0,0 +1,296 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use deno_core::error::type_error;
+use deno_core::error::AnyError;
+use deno_core::op2;
+use deno_core::serde::Deserialize;
+use deno_core::serde::Serialize;
+use deno_core::url::Url;
+use deno_core::ByteString;
+use deno_core::JsBuffer;
+use deno_core::Op;
+use deno_core::OpDecl;
+use deno_core::OpState;
+use deno_core::ResourceId;
+use deno_core::ToJsBuffer;
+use http_v02::header::HeaderName;
+use http_v02::header::HeaderValue;
+use http_v02::HeaderMap;
+use log::debug;
+
+use crate::ext::task_deadline_ms;
+use crate::ext::Permissions;
+use crate::RuntimeLimits;
+
+/// Outbound `fetch` request of the worker, as given to a `FetchInterceptor`.
+pub type OutboundRequest = http_v02::Request<Bytes>;
+
+/// Host closure answering every outbound `fetch` of the worker,
+/// mostly useful to test worker logic without network.
+pub type FetchInterceptor = Arc<dyn Fn(OutboundRequest) -> http_v02::Response<Bytes> + Send + Sync>;
+
+/// Host rewrite of the headers of every outbound `fetch`, run after the worker set
+/// them and before the request is sent (or intercepted), e.g. to strip credentials
+/// sent to third parties or to tag requests with the worker id.
+///
+/// Applied natively to each request leaving the process, redirects followed by
+/// `fetch` and `EventSource` connections included.
+pub type OutboundHeaderPolicy = Arc<dyn Fn(&Url, &mut HeaderMap) + Send + Sync>;
+
+/// Outbound request of a worker that went to the network, once it completed.
+///
+/// Recorded natively for each request that passed the net permission check:
+/// every redirect followed by `fetch` is a record of its own, `EventSource`
+/// connections are recorded too.
+#[derive(Debug, Clone)]
+pub struct EgressRecord {
+    pub host: String,
+    pub port: Option<u16>,
+    pub method: String,
+    /// Status of the response, `None` if the request failed (network error, timeout...).
+    pub status: Option<u16>,
+}
+
+pub type EgressSender = std::sync::mpsc::Sender<EgressRecord>;
+
+/// Outbound requests sent to the network and waiting for their response, by request rid.
+#[derive(Debug, Default)]
+struct EgressPending(HashMap<ResourceId, EgressRecord>);
+
+#[derive(Debug, Deserialize)]
+struct InterceptedRequest {
+    method: String,
+    url: String,
+
+    #[serde(rename = "headerList")]
+    headers: Vec<(String, String)>,
+
+    body: Option<Bytes>,
+}
+
+#[derive(Serialize)]
+struct InterceptedResponse {
+    status: u16,
+
+    #[serde(rename = "headerList")]
+    headers: Vec<(String, String)>,
+
+    body: ToJsBuffer,
+}
+
+/// Outbound requests of the current task, each hop and intercepted request counted.
+#[derive(Debug, Default)]
+pub(crate) struct SubrequestCount(pub(crate) u64);
+
+/// Header set to the time left to the task, see `WorkerOptions::deadline_header`.
+#[derive(Debug, Clone)]
+pub(crate) struct DeadlineHeader(pub(crate) HeaderName);
+
+deno_core::extension!(
+    fetch_intercept,
+    ops = [op_fetch_intercept],
+    middleware = outbound_middleware,
+    state = |state| {
+        state.put::<EgressPending>(EgressPending::default());
+        state.put::<SubrequestCount>(SubrequestCount::default());
+    }
+);
+
+/// Route the network ops of `deno_fetch` through the outbound checks, every
+/// request leaving the process goes through `op_fetch`, one call per hop.
+fn outbound_middleware(op: OpDecl) -> OpDecl {
+    match op.name {
+        "op_fetch" => op.with_implementation_from(&op_outbound_fetch::DECL),
+        "op_fetch_send" => op.with_implementation_from(&op_outbound_fetch_send::DECL),
+        _ => op,
+    }
+}
+
+/// Apply the outbound limits and header policy of the worker to a request.
+///
+/// The header limit counts every header sent, including the `Accept` and
+/// `Accept-Language` defaults added by `fetch`. Headers added by the host
+/// (deadline, policy) are not counted.
+fn outbound_headers(state: &OpState, url: &Url, headers: &mut HeaderMap) -> Result<(), AnyError> {
+    let limits = state.try_borrow::<RuntimeLimits>();
+
+    if let Some(max) = limits.and_then(|limits| limits.max_fetch_headers) {
+        if headers.len() > max {
+            return Err(type_error(format!(
+                "Too many headers in fetch request: {} (max {max})",
+                headers.len()
+            )));
+        }
+    }
+
+    // Time left to the task, for the upstream to give up in time
+    if let Some(DeadlineHeader(name)) = state.try_borrow::<DeadlineHeader>() {
+        let timeout = limits.and_then(|limits| limits.subrequest_timeout_ms);
+
+        let remaining = match (task_deadline_ms(state), timeout) {
+            (Some(remaining), Some(timeout)) => Some(remaining.min(timeout)),
+            (remaining, None) | (None, remaining) => remaining,
+        };
+
+        // Set by the worker itself, sent as is
+        if let (Some(remaining), false) = (remaining, headers.contains_key(name)) {
+            headers.insert(name.clone(), HeaderValue::from(remaining));
+        }
+    }
+
+    // Host policy, last so it sees every header the request will be sent with
+    if let Some(policy) = state.try_borrow::<OutboundHeaderPolicy>() {
+        policy(url, headers);
+    }
+
+    Ok(())
+}
+
+fn header_map<K: AsRef<[u8]>, V: AsRef<[u8]>>(headers: Vec<(K, V)>) -> Result<HeaderMap, AnyError> {
+    let mut map = HeaderMap::new();
+
+    for (k, v) in headers {
+        let name = HeaderName::from_bytes(k.as_ref()).map_err(|err| type_error(err.to_string()))?;
+        let value =
+            HeaderValue::from_bytes(v.as_ref()).map_err(|err| type_error(err.to_string()))?;
+        map.append(name, value);
+    }
+
+    Ok(map)
+}
+
+/// `op_fetch` of `deno_fetch`, with the outbound checks applied to the request.
+#[op2]
+#[serde]
+#[allow(clippy::too_many_arguments)]
+fn op_outbound_fetch(
+    state: &mut OpState,
+    #[serde] method: ByteString,
+    #[string] url: String,
+    #[serde] headers: Vec<(ByteString, ByteString)>,
+    #[smi] client_rid: Option<u32>,
+    has_body: bool,
+    #[buffer] data: Option<JsBuffer>,
+    #[smi] resource: Option<ResourceId>,
+) -> Result<deno_fetch::FetchReturn, AnyError> {
+    let parsed = Url::parse(&url)?;
+
+    let headers = match parsed.scheme() {
+        "http" | "https" => {
+            state.borrow_mut::<SubrequestCount>().0 += 1;
+
+            let mut map = header_map(headers)?;
+            outbound_headers(state, &parsed, &mut map)?;
+
+            map.iter()
+                .map(|(k, v)| (k.as_str().into(), v.as_bytes().into()))
+                .collect()
+        }
+        _ => headers,
+    };
+
+    let record = match parsed.scheme() {
+        "http" | "https" if state.has::<EgressSender>() => Some(EgressRecord {
+            host: parsed.host_str().unwrap_or_default().to_string(),
+            port: parsed.port_or_known_default(),
+            method: String::from_utf8_lossy(&method).into_owned(),
+            status: None,
+        }),
+        _ => None,
+    };
+
+    let res = deno_fetch::op_fetch::<Permissions>::call(
+        state, method, url, headers, client_rid, has_body, data, resource,
+    )?;
+
+    // Past the net permission check, reported once the response arrived
+    if let Some(record) = record {
+        let pending = state.borrow_mut::<EgressPending>();
+        pending.0.insert(res.request_rid, record);
+    }
+
+    Ok(res)
+}
+
+/// `op_fetch_send` of `deno_fetch`, reporting the audited requests once they completed.
+#[op2(async)]
+#[serde]
+async fn op_outbound_fetch_send(
+    state: Rc<RefCell<OpState>>,
+    #[smi] rid: ResourceId,
+) -> Result<deno_fetch::FetchResponse, AnyError> {
+    let record = state
+        .borrow_mut()
+        .borrow_mut::<EgressPending>()
+        .0
+        .remove(&rid);
+
+    let res = deno_fetch::op_fetch_send::call(state.clone(), rid).await;
+
+    if let Some(mut record) = record {
+        record.status = match &res {
+            Ok(res) if res.error.is_none() => Some(res.status),
+            _ => None,
+        };
+
+        if let Some(tx) = state.borrow().try_borrow::<EgressSender>() {
+            if tx.send(record).is_err() {
+                log::warn!("failed to send egress record");
+            }
+        }
+    }
+
+    res
+}
+
+#[op2]
+#[serde]
+fn op_fetch_intercept(
+    state: &mut OpState,
+    #[serde] req: InterceptedRequest,
+) -> Result<InterceptedResponse, AnyError> {
+    debug!("op_fetch_intercept {} {}", req.method, req.url);
+
+    let interceptor = match state.try_borrow::<FetchInterceptor>() {
+        Some(interceptor) => interceptor.clone(),
+        None => return Err(type_error("Fetch interceptor not configured")),
+    };
+
+    let url = Url::parse(&req.url)?;
+
+    state.borrow_mut::<SubrequestCount>().0 += 1;
+
+    let mut headers = header_map(req.headers)?;
+    outbound_headers(state, &url, &mut headers)?;
+
+    let mut builder = http_v02::Request::builder()
+        .method(req.method.as_str())
+        .uri(req.url);
+
+    if let Some(map) = builder.headers_mut() {
+        *map = headers;
+    }
+
+    let res = interceptor(builder.body(req.body.unwrap_or_default())?);
+
+    let headers = res
+        .headers()
+        .iter()
+        .map(|(k, v)| {
+            (
+                k.to_string(),
+                String::from_utf8_lossy(v.as_bytes()).into_owned(),
+            )
+        })
+        .collect();
+
+    Ok(InterceptedResponse {
+        status: res.status().as_u16(),
+        headers,
+        body: res.into_body().to_vec().into(),
+    })
+}
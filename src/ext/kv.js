@@ -0,0 +1,45 @@
+import { core } from "ext:core/mod.js";
+import { op_kv_get, op_kv_put, op_kv_delete, op_kv_list } from "ext:core/ops";
+
+class KvNamespace {
+  async get(key, type = "text") {
+    const value = await op_kv_get(String(key));
+
+    if (value === null) {
+      return null;
+    }
+
+    switch (type) {
+      case "text":
+        return core.decode(value);
+      case "json":
+        return JSON.parse(core.decode(value));
+      case "arrayBuffer":
+        return value.buffer;
+      default:
+        throw new TypeError(`Unsupported KV value type: ${type}`);
+    }
+  }
+
+  async put(key, value) {
+    if (typeof value === "string") {
+      value = core.encode(value);
+    } else if (value instanceof ArrayBuffer) {
+      value = new Uint8Array(value);
+    } else if (!ArrayBuffer.isView(value)) {
+      throw new TypeError("KV value must be a string, ArrayBuffer or view");
+    }
+
+    await op_kv_put(String(key), value);
+  }
+
+  async delete(key) {
+    await op_kv_delete(String(key));
+  }
+
+  async list(options = {}) {
+    return op_kv_list(options.prefix ?? null);
+  }
+}
+
+export { KvNamespace };
@@ -0,0 +1,192 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::Bytes;
+use deno_core::error::type_error;
+use deno_core::error::AnyError;
+use deno_core::futures::future::LocalBoxFuture;
+use deno_core::futures::FutureExt;
+use deno_core::op2;
+use deno_core::Extension;
+use deno_core::ExtensionFileSource;
+use deno_core::JsBuffer;
+use deno_core::OpState;
+use deno_core::ToJsBuffer;
+use log::debug;
+
+use crate::RuntimeLimits;
+
+/// KvStore is the key-value store exposed to the worker as `env.KV`.
+///
+/// The store is implemented by the host; futures are polled on the
+/// worker thread so they don't need to be `Send`.
+pub trait KvStore: Send + Sync {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<Option<Bytes>, AnyError>>;
+
+    fn put<'a>(&'a self, key: &'a str, value: Bytes) -> LocalBoxFuture<'a, Result<(), AnyError>>;
+
+    fn delete<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<(), AnyError>>;
+
+    /// List keys, optionally restricted to those starting with `prefix`.
+    fn list<'a>(
+        &'a self,
+        prefix: Option<&'a str>,
+    ) -> LocalBoxFuture<'a, Result<Vec<String>, AnyError>>;
+}
+
+/// In-memory KvStore, mostly useful for tests and local development.
+#[derive(Debug, Default, Clone)]
+pub struct HashMapKvStore {
+    map: Arc<Mutex<HashMap<String, Bytes>>>,
+}
+
+impl HashMapKvStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KvStore for HashMapKvStore {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<Option<Bytes>, AnyError>> {
+        let value = self.map.lock().unwrap().get(key).cloned();
+
+        async move { Ok(value) }.boxed_local()
+    }
+
+    fn put<'a>(&'a self, key: &'a str, value: Bytes) -> LocalBoxFuture<'a, Result<(), AnyError>> {
+        self.map.lock().unwrap().insert(key.to_string(), value);
+
+        async move { Ok(()) }.boxed_local()
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<(), AnyError>> {
+        self.map.lock().unwrap().remove(key);
+
+        async move { Ok(()) }.boxed_local()
+    }
+
+    fn list<'a>(
+        &'a self,
+        prefix: Option<&'a str>,
+    ) -> LocalBoxFuture<'a, Result<Vec<String>, AnyError>> {
+        let mut keys: Vec<String> = self
+            .map
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix.unwrap_or_default()))
+            .cloned()
+            .collect();
+
+        keys.sort();
+
+        async move { Ok(keys) }.boxed_local()
+    }
+}
+
+deno_core::extension!(
+    kv,
+    deps = [deno_console],
+    ops = [op_kv_get, op_kv_put, op_kv_delete, op_kv_list],
+    customizer = |ext: &mut Extension| {
+        ext.esm_files
+            .to_mut()
+            .push(ExtensionFileSource::new("ext:kv.js", include_str!("kv.js")));
+        ext.esm_entry_point = Some("ext:kv.js");
+    }
+);
+
+/// A KV call didn't complete within `RuntimeLimits::kv_timeout_ms`.
+///
+/// The promise of the call rejects in js realm, the worker can catch it.
+#[derive(Debug)]
+pub struct KvTimeoutError(Duration);
+
+impl std::fmt::Display for KvTimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "KV call timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for KvTimeoutError {}
+
+fn kv_store(state: &Rc<RefCell<OpState>>) -> Result<Arc<dyn KvStore>, AnyError> {
+    match state.borrow().try_borrow::<Arc<dyn KvStore>>() {
+        Some(kv) => Ok(kv.clone()),
+        None => Err(type_error("KV store not configured")),
+    }
+}
+
+/// Wait for a call to the store, up to the KV timeout of the worker if any.
+async fn kv_call<T>(
+    state: &Rc<RefCell<OpState>>,
+    call: LocalBoxFuture<'_, Result<T, AnyError>>,
+) -> Result<T, AnyError> {
+    let timeout = state
+        .borrow()
+        .try_borrow::<RuntimeLimits>()
+        .and_then(|limits| limits.kv_timeout_ms)
+        .map(Duration::from_millis);
+
+    match timeout {
+        None => call.await,
+        Some(timeout) => match tokio::time::timeout(timeout, call).await {
+            Ok(result) => result,
+            Err(_) => Err(KvTimeoutError(timeout).into()),
+        },
+    }
+}
+
+#[op2(async)]
+#[serde]
+async fn op_kv_get(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+) -> Result<Option<ToJsBuffer>, AnyError> {
+    debug!("op_kv_get {key}");
+
+    let kv = kv_store(&state)?;
+
+    let value = kv_call(&state, kv.get(&key)).await?;
+
+    Ok(value.map(|value| value.to_vec().into()))
+}
+
+#[op2(async)]
+async fn op_kv_put(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+    #[buffer] value: JsBuffer,
+) -> Result<(), AnyError> {
+    debug!("op_kv_put {key}");
+
+    let kv = kv_store(&state)?;
+
+    kv_call(&state, kv.put(&key, Bytes::from(value.to_vec()))).await
+}
+
+#[op2(async)]
+async fn op_kv_delete(state: Rc<RefCell<OpState>>, #[string] key: String) -> Result<(), AnyError> {
+    debug!("op_kv_delete {key}");
+
+    let kv = kv_store(&state)?;
+
+    kv_call(&state, kv.delete(&key)).await
+}
+
+#[op2(async)]
+#[serde]
+async fn op_kv_list(
+    state: Rc<RefCell<OpState>>,
+    #[string] prefix: Option<String>,
+) -> Result<Vec<String>, AnyError> {
+    debug!("op_kv_list {prefix:?}");
+
+    let kv = kv_store(&state)?;
+
+    kv_call(&state, kv.list(prefix.as_deref())).await
+}
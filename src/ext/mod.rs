@@ -1,16 +1,59 @@
 mod runtime;
+mod bindings;
 mod permissions;
 mod event_fetch;
 mod event_scheduled;
+mod fetch_intercept;
+mod kv;
 
 pub use runtime::runtime as runtime_ext;
+pub(crate) use runtime::CpuTimeStart;
+pub(crate) use runtime::TaskDeadline;
+pub(crate) use runtime::task_deadline_ms;
+pub(crate) use runtime::LogEventCount;
 pub use runtime::LogEvent;
+pub use runtime::LogFlush;
+pub use runtime::ConsoleOptions;
+pub use runtime::LogLevel;
 
 pub use event_fetch::fetch_event as fetch_event_ext;
 pub use event_fetch::FetchInit;
+pub(crate) use event_fetch::FileResponseRoot;
+pub(crate) use event_fetch::open_streams;
+pub(crate) use event_fetch::respond_default;
+pub(crate) use event_fetch::PassThrough;
+pub(crate) use event_fetch::ResponseStats;
+pub use event_fetch::ContentLengthPolicy;
+pub use event_fetch::InvalidUtf8Policy;
+pub use event_fetch::ResponseBody;
+pub use event_fetch::ResponseStream;
+pub use event_fetch::FileStream;
 
 pub use event_scheduled::scheduled_event as scheduled_event_ext;
 pub use event_scheduled::ScheduledInit;
+pub use event_scheduled::ScheduledOutcome;
+pub use event_scheduled::ScheduledOverlapPolicy;
+pub(crate) use event_scheduled::ScheduledRunning;
+pub(crate) use event_scheduled::scheduled_running;
+pub(crate) use event_scheduled::release_scheduled;
+
+pub use bindings::bindings as bindings_ext;
+pub use bindings::Binding;
+pub(crate) use bindings::Bindings;
+
+pub use fetch_intercept::fetch_intercept as fetch_intercept_ext;
+pub use fetch_intercept::FetchInterceptor;
+pub use fetch_intercept::OutboundRequest;
+pub use fetch_intercept::OutboundHeaderPolicy;
+pub use fetch_intercept::EgressRecord;
+pub use fetch_intercept::EgressSender;
+pub(crate) use fetch_intercept::DeadlineHeader;
+pub(crate) use fetch_intercept::SubrequestCount;
+
+pub use kv::kv as kv_ext;
+pub use kv::HashMapKvStore;
+pub use kv::KvStore;
+pub use kv::KvTimeoutError;
 
 pub use permissions::permissions as permissions_ext;
 pub use permissions::Permissions;
\ No newline at end of file
@@ -3,11 +3,14 @@ use deno_core::url::Url;
 use std::path::Path;
 
 #[derive(Clone)]
-pub struct Permissions {}
+pub struct Permissions {
+    /// Whether outbound network requests are allowed.
+    pub(crate) net: bool,
+}
 
 impl Permissions {
     pub fn new() -> Self {
-        Self {}
+        Self { net: true }
     }
 }
 
@@ -18,8 +21,14 @@ impl deno_web::TimersPermission for Permissions {
 }
 
 impl deno_fetch::FetchPermissions for Permissions {
-    fn check_net_url(&mut self, _url: &Url, _api_name: &str) -> Result<(), AnyError> {
-        Ok(()) // TODO
+    fn check_net_url(&mut self, _url: &Url, api_name: &str) -> Result<(), AnyError> {
+        match self.net {
+            true => Ok(()),
+            false => Err(deno_core::error::custom_error(
+                "PermissionDenied",
+                format!("{api_name} is disabled"),
+            )),
+        }
     }
 
     fn check_read(&mut self, _p: &Path, _api_name: &str) -> Result<(), AnyError> {
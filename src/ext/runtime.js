@@ -1,10 +1,21 @@
 // runtime.js
-import { op_log } from "ext:core/ops";
+import {
+  op_binding_call,
+  op_cpu_time_ms,
+  op_fetch_intercept,
+  op_get_limits,
+  op_log,
+  op_log_flush,
+  op_log_structured,
+  op_sleep,
+  op_timer_handle,
+} from "ext:core/ops";
 
 // deno_core
 import { core, primordials } from "ext:core/mod.js";
 import * as fetchEvent from "ext:event_fetch.js";
 import * as scheduledEvent from "ext:event_scheduled.js";
+import { KvNamespace } from "ext:kv.js";
 
 // deno_webidl
 import * as webidl from "ext:deno_webidl/00_webidl.js";
@@ -45,8 +56,28 @@ import * as fetch from "ext:deno_fetch/26_fetch.js";
 import * as eventSource from "ext:deno_fetch/27_eventsource.js";
 
 {
-  const { ObjectDefineProperties, ObjectDefineProperty, SymbolFor } =
-    primordials;
+  const {
+    ArrayIsArray,
+    ArrayPrototypeMap,
+    ArrayPrototypePush,
+    ArrayPrototypeSlice,
+    ErrorCaptureStackTrace,
+    JSONParse,
+    JSONStringify,
+    MathMax,
+    ObjectDefineProperties,
+    ObjectDefineProperty,
+    ObjectFreeze,
+    ObjectGetPrototypeOf,
+    ObjectKeys,
+    ObjectPrototype,
+    ReflectApply,
+    SafeSet,
+    SetPrototypeAdd,
+    SetPrototypeDelete,
+    SymbolFor,
+    indirectEval,
+  } = primordials;
 
   class WorkerNavigator {
     constructor() {
@@ -144,6 +175,313 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
     }
   }
 
+  let maxPendingTimers,
+    subrequestTimeoutMs,
+    interceptFetch,
+    acceptEncoding;
+
+  // Outbound fetch answered by the host interceptor instead of the network
+  async function interceptedFetch(req) {
+    const body =
+      req.body === null ? null : new Uint8Array(await req.arrayBuffer());
+
+    const res = op_fetch_intercept({
+      method: req.method,
+      url: req.url,
+      headerList: [...req.headers],
+      body,
+    });
+
+    return new response.Response(
+      res.body.byteLength === 0 ? null : res.body,
+      { status: res.status, headers: res.headerList }
+    );
+  }
+
+  // Outbound fetch, the header limits and policy of the host are applied
+  // natively to each request leaving the process (see `op_outbound_fetch`)
+  async function limitedFetch(input, init = undefined) {
+    if (acceptEncoding === "identity") {
+      input = new request.Request(input, init);
+      init = undefined;
+
+      // Set by the worker itself (e.g. proxying the client request)
+      if (!input.headers.has("accept-encoding")) {
+        input.headers.set("accept-encoding", "identity");
+      }
+    }
+
+    if (interceptFetch) {
+      return interceptedFetch(new request.Request(input, init));
+    }
+
+    return networkFetch(input, init);
+  }
+
+  // Outbound fetch to the network, with the subrequest timeout if any
+  async function networkFetch(input, init = undefined) {
+    if (subrequestTimeoutMs === undefined || subrequestTimeoutMs === null) {
+      return fetch.fetch(input, init);
+    }
+
+    // Abort the request (until response headers) if the upstream is too slow,
+    // the rejection can be caught by the worker like any network error.
+    const req = new request.Request(input, init);
+    const controller = new abortSignal.AbortController();
+    const signal = abortSignal.AbortSignal.any([req.signal, controller.signal]);
+
+    const timeout = timers.setTimeout(
+      () =>
+        controller.abort(
+          new TypeError(
+            `NetworkError: fetch timed out after ${subrequestTimeoutMs}ms`
+          )
+        ),
+      subrequestTimeoutMs
+    );
+
+    try {
+      return await fetch.fetch(new request.Request(req, { signal }));
+    } finally {
+      timers.clearTimeout(timeout);
+    }
+  }
+
+  // Outbound fetches waiting for their response headers
+  let pendingFetches = 0;
+
+  // Timers of the worker that haven't fired (timeouts) or been cleared yet
+  const pendingTimers = new SafeSet();
+
+  function checkPendingTimers() {
+    if (
+      maxPendingTimers !== undefined &&
+      maxPendingTimers !== null &&
+      pendingTimers.size >= maxPendingTimers
+    ) {
+      throw new RangeError(
+        `Too many pending timers: ${pendingTimers.size} (max ${maxPendingTimers})`
+      );
+    }
+  }
+
+  function limitedSetTimeout(callback, timeout = 0, ...args) {
+    checkPendingTimers();
+
+    // String callbacks are evaluated like deno_web does, once the timer fired
+    const source =
+      typeof callback === "function"
+        ? undefined
+        : webidl.converters.DOMString(callback);
+
+    const id = timers.setTimeout(
+      (...args) => {
+        SetPrototypeDelete(pendingTimers, id);
+
+        return source === undefined
+          ? ReflectApply(callback, globalThis, args)
+          : indirectEval(source);
+      },
+      timeout,
+      ...args
+    );
+
+    SetPrototypeAdd(pendingTimers, id);
+    return id;
+  }
+
+  function limitedSetInterval(callback, timeout = 0, ...args) {
+    checkPendingTimers();
+
+    const id = timers.setInterval(callback, timeout, ...args);
+
+    SetPrototypeAdd(pendingTimers, id);
+    return id;
+  }
+
+  function limitedClearTimeout(id = 0) {
+    SetPrototypeDelete(pendingTimers, id);
+    timers.clearTimeout(id);
+  }
+
+  function limitedClearInterval(id = 0) {
+    SetPrototypeDelete(pendingTimers, id);
+    timers.clearInterval(id);
+  }
+
+  // Resolves after `delay` milliseconds, rejects with the abort reason as soon
+  // as the signal is aborted. Waiting is a host timer, it doesn't use CPU time.
+  async function wait(delay, options = undefined) {
+    const signal = options?.signal;
+    signal?.throwIfAborted();
+
+    const cancelRid = op_timer_handle();
+    const cancel = () => core.tryClose(cancelRid);
+
+    signal?.addEventListener("abort", cancel, { once: true });
+
+    try {
+      // Resolves to false once the handle is closed
+      const completed = await op_sleep(MathMax(0, delay | 0), cancelRid);
+
+      if (!completed) {
+        throw signal.reason;
+      }
+    } finally {
+      signal?.removeEventListener("abort", cancel);
+      cancel();
+    }
+  }
+
+  async function countedFetch(input, init = undefined) {
+    pendingFetches++;
+
+    try {
+      return await limitedFetch(input, init);
+    } finally {
+      pendingFetches--;
+    }
+  }
+
+  // Host binding: every method call is forwarded to the Rust `Binding`
+  function createBinding(name) {
+    return new Proxy(
+      {},
+      {
+        get(_target, method) {
+          // Not thenable, so the binding can be returned from async functions
+          if (typeof method !== "string" || method === "then") {
+            return undefined;
+          }
+
+          return (...args) => op_binding_call(name, method, args);
+        },
+      }
+    );
+  }
+
+  // Arguments of the console call being printed, if any
+  let consoleArgs = null;
+
+  // Set at bootstrap, the console is created in the snapshot
+  let consoleOptions = { assertLevel: "error", traceStack: true };
+
+  // Levels passed by deno_console to the print function
+  const logLevels = { debug: 0, info: 1, warn: 2, error: 3 };
+  const methodLevels = { log: 1, debug: 0, info: 1, warn: 2, error: 3 };
+
+  // Set at bootstrap, filtered calls return before formatting their arguments
+  let minLogLevel = 0;
+
+  // A plain object logged on its own is forwarded as structured fields
+  function structuredFields(args) {
+    if (args === null || args.length !== 1) {
+      return null;
+    }
+
+    const [value] = args;
+
+    if (typeof value !== "object" || value === null || ArrayIsArray(value)) {
+      return null;
+    }
+
+    const proto = ObjectGetPrototypeOf(value);
+    if (proto !== ObjectPrototype && proto !== null) {
+      return null;
+    }
+
+    try {
+      return JSONParse(JSONStringify(value));
+    } catch {
+      // Not serializable (cycle, bigint...), keep the formatted message only
+      return null;
+    }
+  }
+
+  // Console argument as a JSON value, not serializable values are inspected
+  function serializeArg(value) {
+    try {
+      const json = JSONStringify(value);
+      if (json !== undefined) {
+        return JSONParse(json);
+      }
+    } catch {
+      // Cycle, bigint...
+    }
+
+    return console.inspect(value);
+  }
+
+  function printLog(msg, level) {
+    if (level < minLogLevel) {
+      return;
+    }
+
+    if (consoleArgs === null) {
+      op_log(level, msg);
+      return;
+    }
+
+    op_log_structured(
+      level,
+      msg,
+      structuredFields(consoleArgs),
+      ArrayPrototypeMap(consoleArgs, serializeArg)
+    );
+  }
+
+  // https://choubey.gitbook.io/internals-of-deno/bridge/4.2-print
+  function createConsole() {
+    const workerConsole = new console.Console(printLog);
+
+    for (const method of ["log", "debug", "info", "warn", "error"]) {
+      const print = workerConsole[method];
+
+      workerConsole[method] = (...args) => {
+        if (methodLevels[method] < minLogLevel) {
+          return;
+        }
+
+        consoleArgs = args;
+        try {
+          print(...args);
+        } finally {
+          consoleArgs = null;
+        }
+      };
+    }
+
+    // Failed assertions logged at the configured level
+    workerConsole.assert = (condition = false, ...args) => {
+      if (condition) {
+        return;
+      }
+
+      const log = workerConsole[consoleOptions.assertLevel];
+
+      if (args.length === 0) {
+        log("Assertion failed");
+      } else if (typeof args[0] === "string") {
+        log(`Assertion failed: ${args[0]}`, ...ArrayPrototypeSlice(args, 1));
+      } else {
+        log("Assertion failed:", ...args);
+      }
+    };
+
+    workerConsole.trace = (...args) => {
+      if (!consoleOptions.traceStack) {
+        workerConsole.error("Trace:", ...args);
+        return;
+      }
+
+      const trace = { name: "Trace", message: console.inspectArgs(args) };
+      ErrorCaptureStackTrace(trace, workerConsole.trace);
+      workerConsole.error(trace.stack);
+    };
+
+    return workerConsole;
+  }
+
   function addEventListener(type, listener) {
     if (typeof type !== "string") {
       throw new TypeError("Type must be a string");
@@ -177,12 +515,7 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
   const windowOrWorkerGlobalScope = {
     dispatchEvent: nonEnumerable(globalThisDispatchEvent),
 
-    console: nonEnumerable(
-      // https://choubey.gitbook.io/internals-of-deno/bridge/4.2-print
-      new console.Console((msg, level) =>
-        op_log(level > 1 ? "error" : "log", msg)
-      )
-    ),
+    console: nonEnumerable(createConsole()),
 
     // DOM Exception
     // deno_web - 01 - dom_exception
@@ -191,10 +524,10 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
 
     // Timers
     // deno_web - 02 - timers
-    clearInterval: writable(timers.clearInterval),
-    clearTimeout: writable(timers.clearTimeout),
-    setInterval: writable(timers.setInterval),
-    setTimeout: writable(timers.setTimeout),
+    clearInterval: writable(limitedClearInterval),
+    clearTimeout: writable(limitedClearTimeout),
+    setInterval: writable(limitedSetInterval),
+    setTimeout: writable(limitedSetTimeout),
 
     // Abort signal
     // deno_web - 03 - abort_signal
@@ -266,6 +599,11 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
     performance: writable(performance.performance),
 
     // MessagePort
+    // deno_web - 13 - message_port
+    // A port listening for messages keeps the task running until it is closed
+    MessageChannel: nonEnumerable(messagePort.MessageChannel),
+    MessagePort: nonEnumerable(messagePort.MessagePort),
+    MessageEvent: nonEnumerable(event.MessageEvent),
     structuredClone: writable(messagePort.structuredClone),
 
     // URL
@@ -294,7 +632,7 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
     Response: nonEnumerable(response.Response),
 
     // deno_fetch - 26 - fetch
-    fetch: nonEnumerable(fetch.fetch),
+    fetch: nonEnumerable(countedFetch),
 
     // deno_fetch - 27 - eventsource
     EventSource: nonEnumerable(eventSource.EventSource),
@@ -302,6 +640,21 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
     // Events
     addEventListener: nonEnumerable(addEventListener),
 
+    // Resolves once the host log sink received the previous console calls
+    logFlush: nonEnumerable(() => op_log_flush()),
+
+    // CPU time of the current task, in milliseconds
+    cpuTimeMs: nonEnumerable(() => op_cpu_time_ms()),
+
+    // Number of outbound fetches waiting for their response headers
+    pendingFetches: nonEnumerable(() => pendingFetches),
+
+    // Limits configured by the host, in the units of `RuntimeLimits`
+    getLimits: nonEnumerable(() => op_get_limits()),
+
+    // Scheduling primitives, `await scheduler.wait(ms, { signal })`
+    scheduler: nonEnumerable(ObjectFreeze({ wait })),
+
     // Branding as a WebIDL object
     [webidl.brand]: nonEnumerable(webidl.brand),
   };
@@ -316,6 +669,18 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
 
   let hasBootstrapped = false;
 
+  // Worker env, replaced on reload
+  let workerEnv, kvBinding;
+
+  function setEnv(env) {
+    workerEnv = env;
+
+    if (kvBinding) {
+      workerEnv = workerEnv ?? {};
+      workerEnv.KV = new KvNamespace();
+    }
+  }
+
   core.setUnhandledPromiseRejectionHandler(processUnhandledPromiseRejection);
   core.setHandledPromiseRejectionHandler(processRejectionHandled);
 
@@ -370,7 +735,7 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
     }
   }
 
-  globalThis.bootstrap = (agent, env) => {
+  globalThis.bootstrap = (agent, env, config) => {
     if (hasBootstrapped) {
       throw new Error("Worker runtime already bootstrapped");
     }
@@ -381,6 +746,23 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
     numCpus = 1;
     language = "en-US";
     userAgent = agent ?? "OpenWorkers/0.0.0";
+    maxPendingTimers = config.limits.maxPendingTimers;
+    subrequestTimeoutMs = config.limits.subrequestTimeoutMs;
+    interceptFetch = config.interceptFetch;
+    acceptEncoding = config.acceptEncoding;
+    consoleOptions = config.console;
+    minLogLevel = logLevels[config.minLogLevel];
+
+    // Quiet mode, console calls don't even reach the log ops
+    if (consoleOptions.quiet) {
+      const workerConsole = windowOrWorkerGlobalScope.console.value;
+
+      for (const key of ObjectKeys(workerConsole)) {
+        if (typeof workerConsole[key] === "function") {
+          workerConsole[key] = () => {};
+        }
+      }
+    }
 
     // Delete globalThis.bootstrap (this function)
     delete globalThis.bootstrap;
@@ -397,6 +779,19 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
     // Assign global properties
     ObjectDefineProperties(globalThis, globalProperties);
 
+    // Web APIs disabled by the host
+    if (!config.features.fetch) {
+      delete globalThis.fetch;
+      delete globalThis.EventSource;
+    }
+
+    if (!config.features.crypto) {
+      delete globalThis.crypto;
+      delete globalThis.Crypto;
+      delete globalThis.CryptoKey;
+      delete globalThis.SubtleCrypto;
+    }
+
     // Remove Deno from globalThis
     ObjectDefineProperty(globalThis, "Deno", {
       value: undefined,
@@ -405,9 +800,21 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
       configurable: true,
     });
 
+    // Host provided bindings
+    kvBinding = config.kv;
+    setEnv(env);
+
+    for (const name of config.bindings) {
+      ObjectDefineProperty(globalThis, name, {
+        value: createBinding(name),
+        writable: false,
+        enumerable: true,
+        configurable: false,
+      });
+    }
+
     ObjectDefineProperty(globalThis, "env", {
-      value: env,
-      writable: false,
+      get: () => workerEnv,
       enumerable: true,
       configurable: false,
     });
@@ -428,6 +835,29 @@ import * as eventSource from "ext:deno_fetch/27_eventsource.js";
     return {
       scheduled: scheduledEvent.triggerScheduledEvent,
       fetch: fetchEvent.triggerFetchEvent,
+      // Task types the worker registered a listener for
+      listeners: () => {
+        const types = [];
+
+        if (fetchEvent.hasFetchEventListener()) {
+          ArrayPrototypePush(types, "fetch");
+        }
+
+        if (scheduledEvent.hasScheduledEventListener()) {
+          ArrayPrototypePush(types, "scheduled");
+        }
+
+        return types;
+      },
+      // Clear the event listeners before a script reload, and replace the env if given
+      reset: (...args) => {
+        fetchEvent.unregisterFetchEventListener();
+        scheduledEvent.unregisterScheduledEventListener();
+
+        if (args.length > 0) {
+          setEnv(args[0]);
+        }
+      },
     };
   };
 }
@@ -1,7 +1,17 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use deno_core::futures::future::LocalBoxFuture;
 use deno_core::Extension;
 use deno_core::ExtensionFileSource;
 use deno_core::OpState;
 use deno_core::serde::Serialize;
+use deno_core::serde_json;
+use tokio::time::Instant;
+
+use crate::RuntimeLimits;
 
 deno_core::extension!(
     runtime,
@@ -11,9 +21,17 @@ deno_core::extension!(
         deno_crypto,
         deno_fetch,
         fetch_event,
-        scheduled_event
+        scheduled_event,
+        kv,
+        bindings
+    ],
+    ops = [
+        op_log,
+        op_log_structured,
+        op_log_flush,
+        op_cpu_time_ms,
+        op_get_limits
     ],
-    ops = [op_log],
     customizer = |ext: &mut Extension| {
         ext.esm_files.to_mut().push(ExtensionFileSource::new(
             "ext:runtime.js",
@@ -27,15 +45,186 @@ deno_core::extension!(
 pub struct LogEvent {
     pub level: String,
     pub message: String,
+    /// Fields of a plain object logged on its own, e.g. `console.log({ requestId, latency })`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Arguments of the console call, serialized to JSON
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<serde_json::Value>>,
 }
 
-#[deno_core::op2(fast)]
-fn op_log(state: &mut OpState, #[string] level: &str, #[string] message: &str) {
-    let evt = LogEvent {
-        level: level.to_string(),
-        message: message.to_string(),
+/// Host hook resolving once the log sink has processed the events sent so far.
+///
+/// Events are sent in order on the log channel, so the sink can acknowledge
+/// a flush as soon as it has drained its receiver.
+pub type LogFlush = Arc<dyn Fn() -> LocalBoxFuture<'static, ()> + Send + Sync>;
+
+/// Severity of a console call, ordered from the most verbose.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    #[default]
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Level passed by deno_console to the print function.
+    fn from_console(level: u32) -> Self {
+        match level {
+            0 => LogLevel::Debug,
+            1 => LogLevel::Info,
+            2 => LogLevel::Warn,
+            _ => LogLevel::Error,
+        }
+    }
+
+    /// Level of the emitted `LogEvent`.
+    fn event_level(self) -> String {
+        match self {
+            LogLevel::Debug | LogLevel::Info => "log".to_string(),
+            LogLevel::Warn | LogLevel::Error => "error".to_string(),
+        }
+    }
+}
+
+/// Behavior of the console methods without a direct level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsoleOptions {
+    /// Level of failed `console.assert` calls.
+    pub assert_level: LogLevel,
+
+    /// Log the stack of `console.trace` calls, otherwise only their message.
+    pub trace_stack: bool,
+
+    /// Make every console method a no-op: no log event is sent, and unlike
+    /// `WorkerOptions::min_log_level` the calls don't cross into the host.
+    pub quiet: bool,
+}
+
+impl Default for ConsoleOptions {
+    fn default() -> Self {
+        ConsoleOptions {
+            assert_level: LogLevel::Error,
+            trace_stack: true,
+            quiet: false,
+        }
+    }
+}
+
+/// Number of log events sent during the current `exec`.
+#[derive(Debug, Default)]
+pub(crate) struct LogEventCount {
+    pub(crate) max: Option<u32>,
+    pub(crate) count: u32,
+}
+
+/// Set once the host dropped the log receiver, further events are discarded
+/// without trying to send them.
+#[derive(Debug)]
+struct LogSinkClosed;
+
+/// Whether events of this level pass the worker minimum level and log budget.
+///
+/// The first event over budget is replaced by a single warning.
+fn log_enabled(state: &mut OpState, level: LogLevel) -> bool {
+    if state.has::<LogSinkClosed>() {
+        return false;
+    }
+
+    if let Some(min_level) = state.try_borrow::<LogLevel>() {
+        if level < *min_level {
+            return false;
+        }
+    }
+
+    let (max, count) = match state.try_borrow_mut::<LogEventCount>() {
+        Some(events) => {
+            events.count = events.count.saturating_add(1);
+            (events.max, events.count)
+        }
+        None => return true,
     };
 
+    match max {
+        Some(max) if count > max => {
+            if count == max + 1 {
+                send_log(
+                    state,
+                    LogEvent {
+                        level: LogLevel::Warn.event_level(),
+                        message: format!("log rate limit exceeded ({max} events), dropping logs"),
+                        fields: None,
+                        args: None,
+                    },
+                );
+            }
+
+            false
+        }
+        _ => true,
+    }
+}
+
+#[deno_core::op2(fast)]
+fn op_log(state: &mut OpState, #[smi] level: u32, #[string] message: &str) {
+    let level = LogLevel::from_console(level);
+
+    if !log_enabled(state, level) {
+        return;
+    }
+
+    send_log(
+        state,
+        LogEvent {
+            level: level.event_level(),
+            message: message.to_string(),
+            fields: None,
+            args: None,
+        },
+    );
+}
+
+#[deno_core::op2]
+fn op_log_structured(
+    state: &mut OpState,
+    #[smi] level: u32,
+    #[string] message: &str,
+    #[serde] fields: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde] args: Vec<serde_json::Value>,
+) {
+    let level = LogLevel::from_console(level);
+
+    if !log_enabled(state, level) {
+        return;
+    }
+
+    send_log(
+        state,
+        LogEvent {
+            level: level.event_level(),
+            message: message.to_string(),
+            fields,
+            args: Some(args),
+        },
+    );
+}
+
+/// Wait for the log sink to process the events emitted so far, resolves
+/// immediately if the host provided no flush hook.
+#[deno_core::op2(async)]
+async fn op_log_flush(state: Rc<RefCell<OpState>>) {
+    let flush = state.borrow().try_borrow::<LogFlush>().cloned();
+
+    if let Some(flush) = flush {
+        flush().await;
+    }
+}
+
+fn send_log(state: &mut OpState, evt: LogEvent) {
     log::debug!("op_log {:?}", evt);
 
     let tx = state.try_borrow_mut::<std::sync::mpsc::Sender<LogEvent>>();
@@ -44,7 +233,62 @@ fn op_log(state: &mut OpState, #[string] level: &str, #[string] message: &str) {
         None => log::warn!("failed to borrow log event sender"),
         Some(tx) => match tx.send(evt) {
             Ok(_) => {},
-            Err(_) => log::error!("failed to send log event"),
+            Err(_) => {
+                log::warn!("log event receiver dropped, discarding further logs");
+                state.put(LogSinkClosed);
+            }
         },
     }
 }
+
+/// Thread CPU time when the current `exec` started.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct CpuTimeStart(pub(crate) Duration);
+
+/// CPU time consumed by the worker thread since the current `exec` started.
+#[deno_core::op2(fast)]
+fn op_cpu_time_ms(state: &mut OpState) -> f64 {
+    let start = state
+        .try_borrow::<CpuTimeStart>()
+        .copied()
+        .unwrap_or_default();
+
+    crate::util::thread_cpu_time()
+        .saturating_sub(start.0)
+        .as_secs_f64()
+        * 1000.0
+}
+
+/// Deadlines of the current `exec`, see `WorkerOptions::deadline_header`.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct TaskDeadline {
+    /// End of `RuntimeLimits::headers_timeout_ms`, until the response head is sent.
+    pub(crate) headers: Option<Instant>,
+    /// End of the `RuntimeLimits::lifetime_wall_time_ms` budget left to the worker.
+    pub(crate) wall_time: Option<Instant>,
+}
+
+/// Time left to the current task, in milliseconds, `None` without deadline.
+pub(crate) fn task_deadline_ms(state: &OpState) -> Option<u64> {
+    let deadline = state.try_borrow::<TaskDeadline>().copied()?;
+
+    let responded = state
+        .try_borrow::<crate::ext::ResponseStats>()
+        .is_some_and(|stats| stats.started);
+
+    let headers = deadline.headers.filter(|_| !responded);
+
+    let end = match (headers, deadline.wall_time) {
+        (Some(headers), Some(wall_time)) => headers.min(wall_time),
+        (end, None) | (None, end) => end?,
+    };
+
+    Some(end.saturating_duration_since(Instant::now()).as_millis() as u64)
+}
+
+/// Limits configured for the worker, unset ones are `null`.
+#[deno_core::op2]
+#[serde]
+fn op_get_limits(state: &mut OpState) -> RuntimeLimits {
+    state.try_borrow::<RuntimeLimits>().cloned().unwrap_or_default()
+}
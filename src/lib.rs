@@ -1,6 +1,12 @@
 mod ext;
+mod limits;
+mod loader;
+mod metrics;
+mod pool;
 mod runtime;
+mod static_routes;
 mod task;
+mod url_normalization;
 pub mod snapshot;
 
 pub (crate) mod util;
@@ -9,13 +15,66 @@ pub (crate) use runtime::extensions;
 
 pub use runtime::Script;
 pub use runtime::Worker;
+pub use runtime::WorkerOptions;
+pub use pool::WorkerPool;
+pub use pool::TenantUsage;
+pub use pool::WorkerOptionsFactory;
+pub use runtime::AcceptEncodingPolicy;
+pub use runtime::Features;
+pub use runtime::FetchClientConfig;
+pub use runtime::CpuSoftLimitCallback;
+pub use runtime::RetryPolicy;
+pub use runtime::InspectorOptions;
+pub use runtime::InspectorSessionSender;
+pub use runtime::MicrotaskPolicy;
+pub use runtime::InitTimeoutError;
+pub use runtime::HeadersTimeoutError;
+pub use runtime::PayloadTooLargeError;
+pub use runtime::TerminatedError;
+pub use limits::RuntimeLimits;
+pub use metrics::MetricEvent;
+pub use metrics::MetricsOptions;
+pub use metrics::MetricsSender;
+pub use metrics::TaskMetrics;
+pub use static_routes::DefaultResponse;
+pub use static_routes::StaticRoute;
+pub use url_normalization::TrailingSlash;
+pub use url_normalization::UrlNormalization;
 pub use ext::LogEvent;
+pub use ext::LogFlush;
+pub use ext::LogLevel;
+pub use ext::ConsoleOptions;
 pub use ext::FetchInit;
+pub use ext::Binding;
+pub use ext::FetchInterceptor;
+pub use ext::OutboundRequest;
+pub use ext::OutboundHeaderPolicy;
+pub use ext::EgressRecord;
+pub use ext::EgressSender;
+pub use loader::SourceTransform;
+pub use ext::InvalidUtf8Policy;
+pub use ext::ContentLengthPolicy;
+pub use ext::ResponseBody;
+pub use ext::ResponseStream;
+pub use ext::FileStream;
 pub use ext::ScheduledInit;
+pub use ext::ScheduledOutcome;
+pub use ext::ScheduledOverlapPolicy;
+pub use ext::KvStore;
+pub use ext::HashMapKvStore;
+pub use ext::KvTimeoutError;
 pub use deno_core::error::AnyError;
 pub use deno_core::FastString;
+pub use deno_web::BlobStore;
 pub use task::Task;
 pub use task::TaskType;
+pub use task::TerminationReason;
+pub use task::ErrorSender;
 pub use deno_core::Snapshot;
 pub use deno_core::url::Url;
 pub use runtime::module_url;
+pub use runtime::worker_url;
+pub use runtime::set_stack_size_kb;
+pub use runtime::set_semi_space_size_mb;
+pub use runtime::set_max_workers;
+pub use runtime::init_v8_flags;
@@ -0,0 +1,83 @@
+use deno_core::serde::Serialize;
+
+/// Resource limits applied to a worker.
+///
+/// Every limit is optional, `None` means unlimited.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeLimits {
+    /// Maximum size of the V8 heap, in megabytes.
+    pub heap_max_mb: Option<usize>,
+
+    /// Extra heap granted once `heap_max_mb` is reached, in megabytes, so the
+    /// worker can still respond with an error. Defaults to a tenth of the heap limit.
+    pub heap_grace_mb: Option<usize>,
+
+    /// Maximum number of headers sent on an outbound `fetch` request, checked on
+    /// each redirect. The `Accept` and `Accept-Language` defaults of `fetch` count.
+    pub max_fetch_headers: Option<usize>,
+
+    /// Maximum size of the body of a request dispatched to the worker, in bytes.
+    ///
+    /// Larger requests are refused with a `PayloadTooLargeError` before reaching
+    /// the worker, the host should answer them with a `413 Payload Too Large`.
+    pub max_request_bytes: Option<usize>,
+
+    /// Maximum number of headers of a worker response.
+    pub max_response_headers: Option<usize>,
+
+    /// Maximum total size of the headers of a worker response (names and values), in bytes.
+    pub max_header_bytes: Option<usize>,
+
+    /// Timeout of an outbound `fetch` request (until response headers), in milliseconds.
+    ///
+    /// On expiry the `fetch` promise rejects with a network error the worker can catch.
+    pub subrequest_timeout_ms: Option<u64>,
+
+    /// Timeout of a call to the KV store (`env.KV`), in milliseconds.
+    ///
+    /// On expiry the call rejects with a `KvTimeoutError` the worker can catch.
+    pub kv_timeout_ms: Option<u64>,
+
+    /// Timeout of the worker bootstrap and main module evaluation, in milliseconds.
+    ///
+    /// On expiry `Worker::new` fails with an `InitTimeoutError`, even if top-level
+    /// code is stuck in a loop.
+    pub init_timeout_ms: Option<u64>,
+
+    /// Timeout of the `globalThis.bootstrap` call alone, in milliseconds.
+    ///
+    /// On expiry `Worker::new` fails with an `InitTimeoutError`.
+    pub bootstrap_timeout_ms: Option<u64>,
+
+    /// Time a fetch task has to send its response head (`respondWith`), in milliseconds.
+    ///
+    /// On expiry `exec` fails with a `HeadersTimeoutError` so the host can still answer
+    /// with an error status, e.g. a 504, before a streamed response is committed.
+    pub headers_timeout_ms: Option<u64>,
+
+    /// Wall-clock time a worker may spend in `exec` over its lifetime, in milliseconds.
+    ///
+    /// The task exceeding the budget runs to completion, further tasks are refused.
+    pub lifetime_wall_time_ms: Option<u64>,
+
+    /// CPU time of a task after which `WorkerOptions::on_cpu_soft_limit` is called, in milliseconds.
+    ///
+    /// The task keeps running, the host may stop routing work to the worker.
+    pub cpu_soft_limit_ms: Option<u64>,
+
+    /// `cpu_soft_limit_ms` of scheduled tasks, which usually get a larger budget
+    /// than interactive requests. Falls back to `cpu_soft_limit_ms` if unset.
+    pub scheduled_cpu_soft_limit_ms: Option<u64>,
+
+    /// Maximum number of pending `setTimeout` and `setInterval` timers.
+    ///
+    /// Past the limit, new timers throw a `RangeError` the worker can catch.
+    pub max_pending_timers: Option<u32>,
+
+    /// Maximum number of log events per `exec`, further events are dropped.
+    pub max_log_events: Option<u32>,
+
+    /// Time without `exec` after which `Worker::idle_watch` resolves, in milliseconds.
+    pub idle_timeout_ms: Option<u64>,
+}
@@ -0,0 +1,163 @@
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use deno_core::error::AnyError;
+use deno_core::futures::FutureExt;
+use deno_core::FastString;
+use deno_core::ModuleLoadResponse;
+use deno_core::ModuleLoader;
+use deno_core::ModuleSource;
+use deno_core::ModuleSourceCode;
+use deno_core::ModuleSpecifier;
+use deno_core::ModuleType;
+use deno_core::RequestedModuleType;
+use deno_core::ResolutionKind;
+
+pub(crate) type LoadedModules = Rc<RefCell<Vec<ModuleSpecifier>>>;
+
+/// Host rewrite of JavaScript module sources before V8 compiles them, e.g. to
+/// inject a prelude or instrument functions.
+///
+/// It is given the main module (inline or loaded) and every imported module.
+/// Stack traces point into the transformed source: keep line numbers (e.g. put a
+/// prelude on the first line) or append an inline source map to the result.
+pub type SourceTransform =
+    Arc<dyn Fn(&ModuleSpecifier, String) -> Result<String, AnyError> + Send + Sync>;
+
+/// Apply the source transform to the source of a module given inline.
+pub(crate) fn transform_inline(
+    transform: Option<&SourceTransform>,
+    specifier: &ModuleSpecifier,
+    code: Option<FastString>,
+) -> Result<Option<FastString>, AnyError> {
+    match (transform, code) {
+        (Some(transform), Some(code)) => {
+            let code = transform(specifier, code.as_str().to_string())?;
+            Ok(Some(code.into()))
+        }
+        (_, code) => Ok(code),
+    }
+}
+
+fn transform_module(
+    transform: &SourceTransform,
+    specifier: &ModuleSpecifier,
+    mut source: ModuleSource,
+) -> Result<ModuleSource, AnyError> {
+    if !matches!(source.module_type, ModuleType::JavaScript) {
+        return Ok(source);
+    }
+
+    let code = match &source.code {
+        ModuleSourceCode::String(code) => code.as_str().to_string(),
+        ModuleSourceCode::Bytes(code) => String::from_utf8(code.to_vec())?,
+    };
+
+    let code = transform(specifier, code)?;
+    source.code = ModuleSourceCode::String(code.into());
+
+    Ok(source)
+}
+
+/// Reload query (`reload=N`, see `Worker::reload`) of a referrer.
+fn reload_query(referrer: &str) -> Option<String> {
+    let referrer = ModuleSpecifier::parse(referrer).ok()?;
+
+    referrer
+        .query_pairs()
+        .find(|(key, _)| key == "reload")
+        .map(|(key, value)| format!("{key}={value}"))
+}
+
+/// Module loader recording the specifiers successfully loaded by another loader,
+/// and applying the source transform to the loaded modules.
+pub(crate) struct RecordingModuleLoader {
+    inner: Rc<dyn ModuleLoader>,
+    loaded: LoadedModules,
+    transform: Option<SourceTransform>,
+}
+
+impl RecordingModuleLoader {
+    pub(crate) fn new(
+        inner: Rc<dyn ModuleLoader>,
+        loaded: LoadedModules,
+        transform: Option<SourceTransform>,
+    ) -> Self {
+        Self {
+            inner,
+            loaded,
+            transform,
+        }
+    }
+}
+
+impl ModuleLoader for RecordingModuleLoader {
+    fn resolve(
+        &self,
+        specifier: &str,
+        referrer: &str,
+        kind: ResolutionKind,
+    ) -> Result<ModuleSpecifier, AnyError> {
+        let mut resolved = self.inner.resolve(specifier, referrer, kind)?;
+
+        // Imports of a reloaded module are fresh instances as well
+        if resolved.query().is_none() && matches!(resolved.scheme(), "file" | "http" | "https") {
+            if let Some(reload) = reload_query(referrer) {
+                resolved.set_query(Some(&reload));
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    fn load(
+        &self,
+        module_specifier: &ModuleSpecifier,
+        maybe_referrer: Option<&ModuleSpecifier>,
+        is_dyn_import: bool,
+        requested_module_type: RequestedModuleType,
+    ) -> ModuleLoadResponse {
+        let response = self.inner.load(
+            module_specifier,
+            maybe_referrer,
+            is_dyn_import,
+            requested_module_type,
+        );
+
+        let loaded = self.loaded.clone();
+        let transform = self.transform.clone();
+        let specifier = module_specifier.clone();
+
+        let complete = move |result: Result<ModuleSource, AnyError>| {
+            let result = match (&transform, result) {
+                (Some(transform), Ok(source)) => transform_module(transform, &specifier, source),
+                (_, result) => result,
+            };
+
+            if result.is_ok() {
+                loaded.borrow_mut().push(specifier);
+            }
+
+            result
+        };
+
+        match response {
+            ModuleLoadResponse::Sync(result) => ModuleLoadResponse::Sync(complete(result)),
+            ModuleLoadResponse::Async(future) => {
+                ModuleLoadResponse::Async(future.map(complete).boxed_local())
+            }
+        }
+    }
+
+    fn prepare_load(
+        &self,
+        module_specifier: &ModuleSpecifier,
+        maybe_referrer: Option<String>,
+        is_dyn_import: bool,
+    ) -> Pin<Box<dyn std::future::Future<Output = Result<(), AnyError>>>> {
+        self.inner
+            .prepare_load(module_specifier, maybe_referrer, is_dyn_import)
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+/// Resource usage of the task being executed by a worker.
+#[derive(Debug, Clone)]
+pub struct TaskMetrics {
+    /// CPU time of the worker thread since the task started.
+    pub cpu_time: Duration,
+
+    /// Wall-clock time since the task started.
+    pub wall_time: Duration,
+
+    /// Used V8 heap, in bytes.
+    pub heap_used: usize,
+}
+
+/// Telemetry sent by a worker, separate from its logs.
+#[derive(Debug, Clone)]
+pub enum MetricEvent {
+    /// Interim sample of a running task.
+    Sample(TaskMetrics),
+
+    /// End of a fetch task.
+    RequestCompleted {
+        cpu_ms: u64,
+        wall_ms: u64,
+        /// Status of the response, 0 if the worker did not respond.
+        status: u16,
+        /// Size of the request body, in bytes.
+        bytes_in: u64,
+        /// Size of the response body, in bytes.
+        bytes_out: u64,
+        /// Whether the response head was sent to the host. If the task failed
+        /// afterwards (e.g. mid-stream), an error status can't be sent anymore.
+        response_started: bool,
+        /// Time spent converting the request into the JS `FetchEvent`, in microseconds.
+        init_us: u64,
+    },
+
+    /// End of a scheduled task.
+    ScheduledCompleted { cpu_ms: u64, wall_ms: u64 },
+}
+
+pub type MetricsSender = std::sync::mpsc::Sender<MetricEvent>;
+
+/// Metrics of the tasks executed by a worker.
+#[derive(Debug, Clone)]
+pub struct MetricsOptions {
+    pub tx: MetricsSender,
+
+    /// Interval between interim samples of running tasks, none if unset.
+    ///
+    /// Samples are taken when the worker yields to the event loop, synchronous
+    /// code running longer than the interval delays them.
+    pub interval: Option<Duration>,
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use deno_core::error::generic_error;
+use deno_core::error::AnyError;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+
+use crate::HeadersTimeoutError;
+use crate::LogEvent;
+use crate::Script;
+use crate::Task;
+use crate::TerminatedError;
+use crate::Worker;
+use crate::WorkerOptions;
+
+/// Options of the pool workers, built on the thread of every new worker
+/// (options may hold thread local values, e.g. the module loader).
+pub type WorkerOptionsFactory = Arc<dyn Fn() -> WorkerOptions + Send + Sync>;
+
+type PoolTask = (Task, oneshot::Sender<(Result<(), AnyError>, TenantUsage)>);
+
+/// Resource usage aggregated over the tasks of a tenant, see `WorkerPool::exec_for`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TenantUsage {
+    /// Number of tasks executed, failed ones included.
+    pub tasks: u64,
+
+    /// CPU time of the worker threads spent on the tasks.
+    pub cpu_time: Duration,
+
+    /// Wall-clock time spent on the tasks.
+    pub wall_time: Duration,
+
+    /// Outbound requests of the tasks, each redirect hop and intercepted request counted.
+    pub subrequests: u64,
+
+    /// Highest V8 heap usage measured at the end of a task, in bytes.
+    pub peak_heap_used: usize,
+}
+
+impl TenantUsage {
+    fn add(&mut self, task: &TenantUsage) {
+        self.tasks += task.tasks;
+        self.cpu_time += task.cpu_time;
+        self.wall_time += task.wall_time;
+        self.subrequests += task.subrequests;
+        self.peak_heap_used = self.peak_heap_used.max(task.peak_heap_used);
+    }
+}
+
+/// Fixed number of warm workers running the same script, each on its own thread.
+///
+/// Tasks are dispatched round-robin. A worker under memory pressure, out of
+/// wall time budget, or whose task was terminated or timed out is replaced by
+/// a fresh one once its task completed. Threads stop when the pool is dropped.
+///
+/// Tasks executed with `exec_for` are accounted to a tenant, whose aggregated
+/// usage is reported by `tenant_usage`.
+pub struct WorkerPool {
+    workers: Vec<mpsc::Sender<PoolTask>>,
+    next: AtomicUsize,
+    tenants: Mutex<HashMap<String, TenantUsage>>,
+}
+
+impl WorkerPool {
+    pub fn new(
+        size: usize,
+        mut script: Script,
+        log_tx: Option<std::sync::mpsc::Sender<LogEvent>>,
+        options: WorkerOptionsFactory,
+    ) -> Result<Self, AnyError> {
+        let mut workers = Vec::with_capacity(size);
+
+        for id in 0..size.max(1) {
+            let (task_tx, task_rx) = mpsc::channel(1);
+
+            let script = script.cheap_copy();
+            let log_tx = log_tx.clone();
+            let options = options.clone();
+
+            std::thread::Builder::new()
+                .name(format!("worker-pool-{id}"))
+                .spawn(move || run_worker(script, log_tx, options, task_rx))?;
+
+            workers.push(task_tx);
+        }
+
+        Ok(WorkerPool {
+            workers,
+            next: AtomicUsize::new(0),
+            tenants: Mutex::default(),
+        })
+    }
+
+    /// Number of workers of the pool.
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Execute a task on the next worker, resolves with the result of its `exec`.
+    pub async fn exec(&self, task: Task) -> Result<(), AnyError> {
+        let (result, _) = self.dispatch(task).await?;
+
+        result
+    }
+
+    /// Execute a task like `exec`, and add its resource usage to the tenant's.
+    ///
+    /// A task the pool could not run (thread stopped) is not accounted.
+    pub async fn exec_for(&self, tenant: &str, task: Task) -> Result<(), AnyError> {
+        let (result, usage) = self.dispatch(task).await?;
+
+        let mut tenants = self.tenants.lock().unwrap();
+        tenants.entry(tenant.to_string()).or_default().add(&usage);
+
+        result
+    }
+
+    /// Resource usage of every tenant a task was executed for.
+    pub fn tenant_usage(&self) -> HashMap<String, TenantUsage> {
+        self.tenants.lock().unwrap().clone()
+    }
+
+    async fn dispatch(&self, task: Task) -> Result<(Result<(), AnyError>, TenantUsage), AnyError> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+
+        let (result_tx, result_rx) = oneshot::channel();
+
+        if self.workers[index].send((task, result_tx)).await.is_err() {
+            return Err(generic_error("worker pool thread stopped"));
+        }
+
+        result_rx
+            .await
+            .map_err(|_| generic_error("worker pool thread stopped"))
+    }
+}
+
+fn run_worker(
+    mut script: Script,
+    log_tx: Option<std::sync::mpsc::Sender<LogEvent>>,
+    options: WorkerOptionsFactory,
+    mut task_rx: mpsc::Receiver<PoolTask>,
+) {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(err) => {
+            log::error!("failed to create worker pool runtime: {err}");
+            return;
+        }
+    };
+
+    let local = tokio::task::LocalSet::new();
+
+    local.block_on(&rt, async move {
+        let mut worker: Option<Worker> = None;
+
+        loop {
+            // Warm up before the task arrives, a failed start is retried on the next one
+            if worker.is_none() {
+                match Worker::new(script.cheap_copy(), log_tx.clone(), options()).await {
+                    Ok(new_worker) => worker = Some(new_worker),
+                    Err(err) => log::error!("failed to start pool worker: {err}"),
+                }
+            }
+
+            let (task, result_tx) = match task_rx.recv().await {
+                Some(task) => task,
+                None => break,
+            };
+
+            let start = Instant::now();
+            let cpu_start = crate::util::thread_cpu_time();
+
+            let result = match &mut worker {
+                Some(worker) => worker.exec(task).await,
+                None => Err(generic_error("pool worker failed to start")),
+            };
+
+            let usage = TenantUsage {
+                tasks: 1,
+                cpu_time: crate::util::thread_cpu_time().saturating_sub(cpu_start),
+                wall_time: start.elapsed(),
+                subrequests: worker.as_mut().map_or(0, |worker| worker.subrequests()),
+                peak_heap_used: worker.as_mut().map_or(0, |worker| worker.heap_used()),
+            };
+
+            // Terminated mid-task, or left with the timed out task still running
+            let interrupted = result.as_ref().is_err_and(|err| {
+                err.downcast_ref::<TerminatedError>().is_some()
+                    || err.downcast_ref::<HeadersTimeoutError>().is_some()
+            });
+
+            // The caller may have stopped waiting
+            let _ = result_tx.send((result, usage));
+
+            if let Some(current) = &worker {
+                if interrupted || current.memory_pressure() || current.wall_time_exhausted() {
+                    log::info!("recycling pool worker");
+                    worker = None;
+                }
+            }
+        }
+    });
+}
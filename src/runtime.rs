@@ -1,34 +1,207 @@
+use crate::ext::bindings_ext;
 use crate::ext::fetch_event_ext;
+use crate::ext::fetch_intercept_ext;
+use crate::ext::kv_ext;
+use crate::ext::open_streams;
 use crate::ext::permissions_ext;
+use crate::ext::release_scheduled;
+use crate::ext::respond_default;
 use crate::ext::runtime_ext;
 use crate::ext::scheduled_event_ext;
+use crate::ext::scheduled_running;
+use crate::ext::Bindings;
+use crate::ext::ContentLengthPolicy;
+use crate::ext::CpuTimeStart;
+use crate::ext::DeadlineHeader;
+use crate::ext::EgressSender;
+use crate::ext::FetchInterceptor;
+use crate::ext::FileResponseRoot;
+use crate::ext::InvalidUtf8Policy;
+use crate::ext::KvStore;
+use crate::ext::LogEventCount;
+use crate::ext::OutboundHeaderPolicy;
+use crate::ext::PassThrough;
 use crate::ext::Permissions;
+use crate::ext::ResponseStats;
+use crate::ext::ScheduledOverlapPolicy;
+use crate::ext::ScheduledRunning;
+use crate::ext::SubrequestCount;
+use crate::ext::TaskDeadline;
+use crate::loader::transform_inline;
+use crate::loader::LoadedModules;
+use crate::loader::RecordingModuleLoader;
+use crate::loader::SourceTransform;
+use crate::Binding;
+use crate::ConsoleOptions;
+use crate::DefaultResponse;
 use crate::LogEvent;
+use crate::LogFlush;
+use crate::LogLevel;
+use crate::MetricEvent;
+use crate::MetricsOptions;
+use crate::RuntimeLimits;
+use crate::StaticRoute;
 use crate::Task;
+use crate::TaskMetrics;
+use crate::TaskType;
+use crate::TerminationReason;
+use crate::UrlNormalization;
 
+use std::cell::Cell;
+use std::fs::File;
+use std::future::Future;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::time::Duration;
 
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+
+use deno_core::error::generic_error;
+use deno_core::error::type_error;
 use deno_core::error::AnyError;
+use deno_core::error::JsError;
+use deno_core::futures::channel::mpsc::UnboundedSender;
+use deno_core::serde::Serialize;
+use deno_core::InspectorSessionProxy;
 use deno_core::JsRuntime;
+use deno_core::ModuleLoader;
+use deno_core::ModuleResolutionError;
+use deno_core::ResourceId;
 
 use deno_core::url::Url;
 use deno_core::v8;
 use deno_core::Snapshot;
 
+use http_v02::header::HeaderName;
 use log::debug;
 
 const USER_AGENT: &str = concat!("OpenWorkers/", env!("CARGO_PKG_VERSION"));
 
+#[cfg(not(feature = "no-snapshot"))]
 const RUNTIME_SNAPSHOT: &[u8] = include_bytes!(env!("RUNTIME_SNAPSHOT_PATH"));
 
+#[cfg(feature = "no-snapshot")]
+const RUNTIME_SNAPSHOT: &[u8] = &[];
+
 pub(crate) fn user_agent() -> String {
     USER_AGENT.to_string()
 }
 
-pub fn module_url(path_str: &str) -> Url {
-    let current_dir = std::env::current_dir().unwrap();
+/// Resolve a worker path relative to the current directory.
+///
+/// Fails if the current directory is not accessible (e.g. deleted) or the path is invalid.
+pub fn module_url(path_str: &str) -> Result<Url, AnyError> {
+    let current_dir = std::env::current_dir()?;
     let current_dir = current_dir.as_path();
-    deno_core::resolve_path(path_str, current_dir).unwrap()
+    Ok(deno_core::resolve_path(path_str, current_dir)?)
+}
+
+/// Virtual specifier of a worker script given inline, e.g. `worker:///my-worker/worker.js`.
+///
+/// Unlike `module_url`, it doesn't depend on the process current directory, and
+/// distinct ids give distinct specifiers (and stack traces) to workers sharing a
+/// process. Relative imports resolve under the id, they need a `module_loader`
+/// able to load `worker:` specifiers.
+pub fn worker_url(worker_id: &str) -> Url {
+    let mut url = Url::parse("worker:///").expect("valid base url");
+
+    url.path_segments_mut()
+        .expect("base url has a path")
+        .pop_if_empty()
+        .push(worker_id)
+        .push("worker.js");
+
+    url
+}
+
+/// Set the V8 stack size limit, in kilobytes.
+///
+/// Recursion deeper than this limit throws a catchable `RangeError` instead of
+/// overflowing the native stack, so it must stay below the stack size of the
+/// threads running workers. The limit is process wide and only applies to
+/// isolates created after the call, call it before the first `Worker::new`.
+///
+/// Sets the V8 flags of the process with `init_v8_flags`, and fails the same
+/// way if they are already set: to combine it with other flags, pass
+/// `--stack-size` to `init_v8_flags` along with them.
+pub fn set_stack_size_kb(size_kb: usize) -> Result<(), AnyError> {
+    init_v8_flags(vec![format!("--stack-size={size_kb}")])
+}
+
+/// Set the maximum size of a V8 young generation semi-space, in megabytes.
+///
+/// Short lived objects are allocated in the young generation, a larger one
+/// means fewer (but longer) scavenges for allocation heavy workers, at the cost
+/// of up to three times this size of memory per isolate. The size is process
+/// wide and only applies to isolates created after the call, call it before the
+/// first `Worker::new`.
+///
+/// Sets the V8 flags of the process with `init_v8_flags`, see `set_stack_size_kb`.
+pub fn set_semi_space_size_mb(size_mb: usize) -> Result<(), AnyError> {
+    init_v8_flags(vec![format!("--max-semi-space-size={size_mb}")])
+}
+
+/// V8 flags of the process, see `init_v8_flags`.
+static V8_FLAGS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Set the V8 command line flags of the process, e.g. `--jitless`.
+///
+/// Flags can only be set once: a later call, or a worker with different
+/// `WorkerOptions::v8_flags`, fails. Some flags are only read when the V8
+/// platform starts, call it before the first `Worker::new`.
+pub fn init_v8_flags(flags: Vec<String>) -> Result<(), AnyError> {
+    let applied = V8_FLAGS.get_or_init(|| {
+        let args = std::iter::once(String::new()).chain(flags.iter().cloned());
+
+        for arg in deno_core::v8_set_flags(args.collect()).iter().skip(1) {
+            log::warn!("unrecognized V8 flag: {arg}");
+        }
+
+        flags.clone()
+    });
+
+    match *applied == flags {
+        true => Ok(()),
+        false => Err(generic_error(format!(
+            "V8 flags already set to {applied:?}, cannot apply {flags:?}"
+        ))),
+    }
+}
+
+/// Apply the V8 flags of a worker, workers without flags keep the process ones.
+fn apply_v8_flags(flags: &[String]) -> Result<(), AnyError> {
+    match flags.is_empty() {
+        true => Ok(()),
+        false => init_v8_flags(flags.to_vec()),
+    }
+}
+
+/// Limit of live workers in the process, see `set_max_workers`.
+static WORKER_SLOTS: OnceLock<Arc<Semaphore>> = OnceLock::new();
+
+/// Limit the number of live `Worker` instances in the process.
+///
+/// Isolates share the process V8 platform and each reserves its own heap, so
+/// `Worker::new` waits for a slot once `max_workers` workers are alive; a slot
+/// is released when its worker is dropped. The limit can only be set once,
+/// call it before the first `Worker::new`.
+pub fn set_max_workers(max_workers: usize) {
+    if WORKER_SLOTS
+        .set(Arc::new(Semaphore::new(max_workers)))
+        .is_err()
+    {
+        log::warn!("max workers already set, ignoring {max_workers}");
+    }
 }
 
 pub(crate) fn runtime_snapshot() -> Option<Snapshot> {
@@ -38,16 +211,17 @@ pub(crate) fn runtime_snapshot() -> Option<Snapshot> {
     }
 }
 
-pub(crate) fn extensions(for_snapshot: bool) -> Vec<deno_core::Extension> {
+pub(crate) fn extensions(
+    for_snapshot: bool,
+    random_seed: Option<u64>,
+    blob_store: Option<Arc<deno_web::BlobStore>>,
+) -> Vec<deno_core::Extension> {
     let mut exts = vec![
         deno_webidl::deno_webidl::init_ops_and_esm(),
         deno_console::deno_console::init_ops_and_esm(),
         deno_url::deno_url::init_ops_and_esm(),
-        deno_web::deno_web::init_ops_and_esm::<Permissions>(
-            std::sync::Arc::new(deno_web::BlobStore::default()),
-            None,
-        ),
-        deno_crypto::deno_crypto::init_ops_and_esm(None),
+        deno_web::deno_web::init_ops_and_esm::<Permissions>(blob_store.unwrap_or_default(), None),
+        deno_crypto::deno_crypto::init_ops_and_esm(random_seed),
         deno_fetch::deno_fetch::init_ops_and_esm::<Permissions>(deno_fetch::Options {
             user_agent: user_agent(),
             ..Default::default()
@@ -55,8 +229,11 @@ pub(crate) fn extensions(for_snapshot: bool) -> Vec<deno_core::Extension> {
         // OpenWorkers extensions
         fetch_event_ext::init_ops_and_esm(),
         scheduled_event_ext::init_ops_and_esm(),
+        kv_ext::init_ops_and_esm(),
         runtime_ext::init_ops_and_esm(),
         permissions_ext::init_ops(),
+        fetch_intercept_ext::init_ops(),
+        bindings_ext::init_ops(),
     ];
 
     if !for_snapshot {
@@ -72,31 +249,605 @@ pub(crate) fn extensions(for_snapshot: bool) -> Vec<deno_core::Extension> {
     exts
 }
 
+/// Normalize the worker env before it is inlined in the bootstrap script.
+///
+/// The env is parsed and serialized again so nothing but a JSON object (no code,
+/// no `NaN` or `Infinity`) can end up in the script.
+fn env_json(env: &str) -> Result<String, AnyError> {
+    match deno_core::serde_json::from_str::<deno_core::serde_json::Value>(env) {
+        Ok(env) if env.is_object() => Ok(env.to_string()),
+        Ok(_) => Err(type_error("Worker env must be a JSON object")),
+        Err(err) => Err(type_error(format!("Worker env is not valid JSON: {err}"))),
+    }
+}
+
 pub struct Script {
     pub specifier: deno_core::ModuleSpecifier,
     pub code: Option<deno_core::ModuleCodeString>,
+    /// JSON object exposed to the worker as `env`.
     pub env: Option<String>,
 }
 
+impl Script {
+    /// Copy the script, moving the code to shared storage so both copies are cheap.
+    pub(crate) fn cheap_copy(&mut self) -> Script {
+        let code = self.code.take().map(|code| {
+            let (code, copy) = code.into_cheap_copy();
+            self.code = Some(code);
+            copy
+        });
+
+        Script {
+            specifier: self.specifier.clone(),
+            code,
+            env: self.env.clone(),
+        }
+    }
+}
+
+/// Retries of a failed worker initialization.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt.
+    pub max_retries: u32,
+
+    /// Delay before the first retry, doubled for each following one.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn delay(&self, retry: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(retry))
+    }
+}
+
+/// Host callback fired once per task when its CPU time crosses
+/// `RuntimeLimits::cpu_soft_limit_ms`, given the CPU time of the task so far.
+pub type CpuSoftLimitCallback = Arc<dyn Fn(Duration) + Send + Sync>;
+
+/// How often the CPU time of a running task is compared to the soft limit.
+const CPU_SOFT_LIMIT_CHECK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Channel accepting Chrome DevTools protocol sessions for the worker isolate.
+pub type InspectorSessionSender = UnboundedSender<InspectorSessionProxy>;
+
+/// V8 inspector attached to the worker isolate, meant for debugging only.
+///
+/// The runtime doesn't listen on any port: `register` is given the session
+/// channel of the isolate so the host can bridge it to a DevTools websocket.
+#[derive(Clone)]
+pub struct InspectorOptions {
+    /// Called once the isolate is created, before the main module is loaded.
+    pub register: Arc<dyn Fn(InspectorSessionSender) + Send + Sync>,
+
+    /// Block until a debugger attaches before evaluating the main module, and
+    /// keep polling the event loop while a session is connected.
+    ///
+    /// This blocks the worker thread, never enable it outside of development.
+    pub wait_for_session: bool,
+}
+
+/// Worker initialization didn't complete within `RuntimeLimits::init_timeout_ms`,
+/// or the bootstrap within `RuntimeLimits::bootstrap_timeout_ms`.
+#[derive(Debug)]
+pub struct InitTimeoutError(Duration);
+
+impl std::fmt::Display for InitTimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "worker initialization timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InitTimeoutError {}
+
+/// A fetch task didn't send its response head within `RuntimeLimits::headers_timeout_ms`.
+#[derive(Debug)]
+pub struct HeadersTimeoutError(Duration);
+
+impl std::fmt::Display for HeadersTimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "worker did not respond within {:?}", self.0)
+    }
+}
+
+impl std::error::Error for HeadersTimeoutError {}
+
+/// A fetch task was refused because its request body is larger than
+/// `RuntimeLimits::max_request_bytes`.
+#[derive(Debug)]
+pub struct PayloadTooLargeError {
+    pub len: usize,
+    pub max: usize,
+}
+
+impl std::fmt::Display for PayloadTooLargeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "request body too large: {} bytes (max {})",
+            self.len, self.max
+        )
+    }
+}
+
+impl std::error::Error for PayloadTooLargeError {}
+
+/// The isolate execution was forcibly terminated while a task was dispatched,
+/// e.g. by the heap limit, rather than failing on a worker exception.
+#[derive(Debug)]
+pub struct TerminatedError;
+
+impl std::fmt::Display for TerminatedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "worker execution was terminated")
+    }
+}
+
+impl std::error::Error for TerminatedError {}
+
+/// Terminates the isolate execution unless dropped before its deadline.
+///
+/// Unlike a tokio timeout, it also interrupts synchronous code (e.g. a top-level
+/// `while (true) {}`) blocking the worker thread.
+struct TerminationWatchdog {
+    deadline: Instant,
+    state: Arc<AtomicU8>,
+    _cancel: std::sync::mpsc::Sender<()>,
+}
+
+const WATCHDOG_WATCHING: u8 = 0;
+const WATCHDOG_FIRED: u8 = 1;
+const WATCHDOG_CANCELLED: u8 = 2;
+
+impl TerminationWatchdog {
+    fn new(handle: v8::IsolateHandle, timeout: Duration) -> Self {
+        let (cancel, cancelled) = std::sync::mpsc::channel::<()>();
+        let state = Arc::new(AtomicU8::new(WATCHDOG_WATCHING));
+
+        {
+            let state = state.clone();
+
+            std::thread::spawn(move || {
+                // Disconnected once the watchdog is dropped
+                if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(timeout) {
+                    let fire = state.compare_exchange(
+                        WATCHDOG_WATCHING,
+                        WATCHDOG_FIRED,
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    );
+
+                    if fire.is_ok() {
+                        handle.terminate_execution();
+                    }
+                }
+            });
+        }
+
+        TerminationWatchdog {
+            deadline: Instant::now() + timeout,
+            state,
+            _cancel: cancel,
+        }
+    }
+
+    fn fired(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == WATCHDOG_FIRED
+    }
+
+    /// Stop watching, `false` if the watchdog fired first: the termination is
+    /// then pending and the isolate can't run any more code.
+    fn cancel(&self) -> bool {
+        let cancel = self.state.compare_exchange(
+            WATCHDOG_WATCHING,
+            WATCHDOG_CANCELLED,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+
+        !matches!(cancel, Err(WATCHDOG_FIRED))
+    }
+}
+
+/// Whether a failed initialization may succeed if attempted again.
+///
+/// Errors raised by the script itself (syntax errors, uncaught exceptions, init
+/// timeouts) and unresolvable specifiers are permanent, loader failures are transient.
+fn is_transient(err: &AnyError) -> bool {
+    if err.downcast_ref::<JsError>().is_some()
+        || err.downcast_ref::<InitTimeoutError>().is_some()
+        || err.downcast_ref::<ModuleResolutionError>().is_some()
+    {
+        return false;
+    }
+
+    match err.downcast_ref::<std::io::Error>() {
+        Some(err) => !matches!(
+            err.kind(),
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied
+        ),
+        None => true,
+    }
+}
+
+/// When V8 runs promise callbacks (microtasks).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MicrotaskPolicy {
+    /// Run microtasks as soon as the JS call stack is empty (V8 default):
+    /// promise callbacks run right after the code that resolved them, before
+    /// any timer or op completion.
+    #[default]
+    Auto,
+    /// Only run microtasks at the checkpoints of the event loop: promise callbacks
+    /// are batched after each turn, which makes ordering against ops and timers
+    /// predictable but delays them until the current task yields to the loop.
+    ///
+    /// deno_core restores `Auto` while reporting an exception, the policy is
+    /// applied again before each `exec`.
+    Explicit,
+}
+
+impl MicrotaskPolicy {
+    fn apply(self, js_runtime: &mut JsRuntime) {
+        let policy = match self {
+            MicrotaskPolicy::Auto => v8::MicrotasksPolicy::Auto,
+            MicrotaskPolicy::Explicit => v8::MicrotasksPolicy::Explicit,
+        };
+
+        js_runtime.v8_isolate().set_microtasks_policy(policy);
+    }
+}
+
+/// Accept-Encoding of the outbound `fetch` requests that don't set one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AcceptEncodingPolicy {
+    /// Advertise gzip and brotli, responses are decompressed before reaching the worker.
+    #[default]
+    Auto,
+    /// Ask upstreams for uncompressed responses, so a proxying worker doesn't
+    /// decompress a body its client won't get compressed anyway.
+    ///
+    /// Requests setting `Accept-Encoding` themselves (e.g. `fetch(event.request)`
+    /// mirroring the client) are sent as is, and their responses aren't decompressed.
+    Identity,
+}
+
+/// Web APIs exposed to the worker, all enabled by default.
+///
+/// Features are not applied in `extensions()`: disabled APIs are removed from the
+/// global scope at bootstrap, but their extensions and ops stay loaded. Other APIs
+/// depend on them, and the runtime snapshot is built once with every extension,
+/// so an isolate with fewer extensions wouldn't match it. Only `fetch` is also
+/// enforced natively, by the network permission; a disabled `crypto` is hidden,
+/// not unloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Features {
+    /// Outbound `fetch` and `EventSource`, network access is denied when disabled.
+    pub fetch: bool,
+
+    /// `crypto` and the Web Crypto interfaces.
+    pub crypto: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            fetch: true,
+            crypto: true,
+        }
+    }
+}
+
+/// Connection reuse of the worker outbound `fetch` client, unset fields keep
+/// the client defaults.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FetchClientConfig {
+    /// Idle connections kept per upstream host.
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// Time an idle connection is kept open, 90 seconds by default.
+    pub pool_idle_timeout_ms: Option<u64>,
+
+    /// Only speak HTTP/2, without negotiation: for upstreams known to support it.
+    pub http2_prior_knowledge: bool,
+}
+
+impl FetchClientConfig {
+    fn create_client(&self) -> Result<deno_fetch::reqwest::Client, AnyError> {
+        deno_fetch::create_http_client(
+            &user_agent(),
+            deno_fetch::CreateHttpClientOptions {
+                pool_max_idle_per_host: self.pool_max_idle_per_host,
+                pool_idle_timeout: self.pool_idle_timeout_ms.map(Some),
+                http1: !self.http2_prior_knowledge,
+                http2: true,
+                ..Default::default()
+            },
+        )
+    }
+}
+
+/// Optional features and bindings of a worker.
+#[derive(Default, Clone)]
+pub struct WorkerOptions {
+    /// Store exposed to the worker as `env.KV`.
+    pub kv: Option<Arc<dyn KvStore>>,
+
+    pub limits: RuntimeLimits,
+
+    /// Handler of outbound `fetch` calls, replacing the network.
+    pub fetch_interceptor: Option<FetchInterceptor>,
+
+    /// Rewrite of the headers of outbound `fetch` calls.
+    pub outbound_headers: Option<OutboundHeaderPolicy>,
+
+    /// Audit of the outbound requests that went to the network, see `EgressRecord`.
+    pub egress_audit: Option<EgressSender>,
+
+    /// Header set on outbound `fetch` calls to the time left to the task, in
+    /// milliseconds (e.g. `x-deadline-ms`), so upstreams can give up in time.
+    ///
+    /// The time left is the earliest of `RuntimeLimits::headers_timeout_ms` (until
+    /// the worker responded), the `lifetime_wall_time_ms` budget left and the
+    /// `subrequest_timeout_ms` of the fetch. Without any of them, or if the
+    /// worker set the header itself, the request is left untouched.
+    pub deadline_header: Option<String>,
+
+    /// Requests answered by the host without dispatching to the worker, first match wins.
+    pub static_routes: Vec<StaticRoute>,
+
+    /// Response sent when a fetch task completes without the worker responding.
+    pub default_response: DefaultResponse,
+
+    /// Normalization of request URLs, applied before static routes are matched.
+    pub url_normalization: UrlNormalization,
+
+    /// Directory trusted workers may serve files from with `event.respondWithFile`,
+    /// the file is streamed by the host as a `ResponseBody::File`. Disabled if `None`.
+    pub file_response_root: Option<PathBuf>,
+
+    /// Check applied to buffered text responses.
+    pub invalid_utf8_policy: InvalidUtf8Policy,
+
+    /// Check of the body length against a declared `Content-Length`, buffered
+    /// and streamed responses.
+    pub content_length_policy: ContentLengthPolicy,
+
+    /// Loader of the main module and its imports, defaults to the file system.
+    pub module_loader: Option<Rc<dyn ModuleLoader>>,
+
+    /// Rewrite of module sources before compilation.
+    pub source_transform: Option<SourceTransform>,
+
+    /// Handling of a scheduled task dispatched while another one is running.
+    pub scheduled_overlap: ScheduledOverlapPolicy,
+
+    /// Acknowledgment of the log sink, awaited by `logFlush()` in the worker.
+    pub log_flush: Option<LogFlush>,
+
+    /// Telemetry of the executed tasks.
+    pub metrics: Option<MetricsOptions>,
+
+    /// Microtask checkpoint policy of the isolate.
+    pub microtask_policy: MicrotaskPolicy,
+
+    /// Console calls below this level are dropped in js realm, before their
+    /// arguments are formatted or sent to the log channel.
+    pub min_log_level: LogLevel,
+
+    /// Behavior of `console.assert` and `console.trace`.
+    pub console: ConsoleOptions,
+
+    /// Retries of the main module load and evaluation on transient errors.
+    pub init_retry: RetryPolicy,
+
+    /// Debugger access to the isolate, disabled if `None`.
+    pub inspector: Option<InspectorOptions>,
+
+    /// Capability objects exposed to the worker as globals named after them.
+    pub bindings: Vec<Arc<dyn Binding>>,
+
+    /// Accept-Encoding of the outbound `fetch` requests that don't set one.
+    pub fetch_accept_encoding: AcceptEncodingPolicy,
+
+    /// Called when a task crosses `RuntimeLimits::cpu_soft_limit_ms`, e.g. to shed load.
+    pub on_cpu_soft_limit: Option<CpuSoftLimitCallback>,
+
+    /// Web APIs exposed to the worker.
+    pub features: Features,
+
+    /// V8 command line flags, e.g. `--jitless` to run untrusted code without JIT
+    /// (which also disables WebAssembly).
+    ///
+    /// V8 flags are process wide: the first worker with flags applies them (unless
+    /// `init_v8_flags` was called), later workers with other flags fail to start.
+    /// Workers without flags always start. Prefer `init_v8_flags` at startup.
+    pub v8_flags: Vec<String>,
+
+    /// Connection pooling of outbound `fetch`, the deno_fetch defaults if unset.
+    pub fetch_client: Option<FetchClientConfig>,
+
+    /// Seed of `crypto.getRandomValues` and `crypto.randomUUID`, making them deterministic.
+    ///
+    /// For tests and local development only: seeded values are predictable,
+    /// never set it for workers serving real traffic.
+    pub random_seed: Option<u64>,
+
+    /// Store of the `Blob` contents and object URLs, a new store per worker if unset.
+    ///
+    /// A store can be shared by several workers, blobs are kept until every
+    /// `Blob` referencing them was collected (or `BlobStore::clear` is called).
+    pub blob_store: Option<Arc<deno_web::BlobStore>>,
+}
+
+/// Configuration passed to `globalThis.bootstrap` in js realm.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BootstrapConfig {
+    kv: bool,
+    intercept_fetch: bool,
+    limits: RuntimeLimits,
+    bindings: Vec<String>,
+    accept_encoding: AcceptEncodingPolicy,
+    features: Features,
+    console: ConsoleOptions,
+    min_log_level: LogLevel,
+}
+
+impl BootstrapConfig {
+    fn new(options: &WorkerOptions) -> Self {
+        BootstrapConfig {
+            kv: options.kv.is_some(),
+            intercept_fetch: options.fetch_interceptor.is_some(),
+            limits: options.limits.clone(),
+            bindings: options
+                .bindings
+                .iter()
+                .map(|binding| binding.name().to_string())
+                .collect(),
+            accept_encoding: options.fetch_accept_encoding,
+            features: options.features,
+            console: options.console,
+            min_log_level: options.min_log_level,
+        }
+    }
+}
+
 pub struct Worker {
     pub(crate) js_runtime: deno_core::JsRuntime,
     pub(crate) trigger_fetch: deno_core::v8::Global<deno_core::v8::Function>,
     pub(crate) trigger_scheduled: deno_core::v8::Global<deno_core::v8::Function>,
+    pub(crate) trigger_reset: deno_core::v8::Global<deno_core::v8::Function>,
+    pub(crate) trigger_listeners: deno_core::v8::Global<deno_core::v8::Function>,
+    pub(crate) listeners: Vec<TaskType>,
+    pub(crate) reloads: u32,
+    pub(crate) memory_pressure: Rc<Cell<bool>>,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) headers_timeout: Option<Duration>,
+    pub(crate) last_exec: Rc<Cell<Instant>>,
+    pub(crate) loaded_modules: LoadedModules,
+    pub(crate) microtask_policy: MicrotaskPolicy,
+    pub(crate) wall_time: Duration,
+    pub(crate) wall_time_budget: Option<Duration>,
+    pub(crate) static_routes: Vec<StaticRoute>,
+    pub(crate) default_response: DefaultResponse,
+    pub(crate) url_normalization: UrlNormalization,
+    pub(crate) metrics: Option<MetricsOptions>,
+    pub(crate) wait_for_inspector: bool,
+    pub(crate) cpu_soft_limit: Option<Duration>,
+    pub(crate) scheduled_cpu_soft_limit: Option<Duration>,
+    pub(crate) on_cpu_soft_limit: Option<CpuSoftLimitCallback>,
+    pub(crate) source_transform: Option<SourceTransform>,
+    // Last field: released once the isolate is dropped
+    pub(crate) _slot: Option<OwnedSemaphorePermit>,
+}
+
+/// Install a near heap limit callback flagging the worker under memory pressure.
+///
+/// The first time V8 approaches the limit, the heap is given `grace` more bytes
+/// (a tenth of the limit if unset) so the worker can fail gracefully. If the limit
+/// is approached again, execution is terminated (still with some headroom so V8
+/// can unwind instead of aborting).
+fn watch_heap_limit(
+    js_runtime: &mut JsRuntime,
+    memory_pressure: Rc<Cell<bool>>,
+    grace: Option<usize>,
+) {
+    let handle = js_runtime.v8_isolate().thread_safe_handle();
+
+    js_runtime.add_near_heap_limit_callback(move |current, initial| {
+        if memory_pressure.replace(true) {
+            log::error!("heap limit reached again ({current} bytes), terminating execution");
+            handle.terminate_execution();
+        } else {
+            log::warn!("heap is near its limit ({current} bytes)");
+        }
+
+        current + grace.unwrap_or(initial / 10)
+    });
 }
 
 impl Worker {
     pub async fn new(
+        mut script: Script,
+        log_tx: Option<std::sync::mpsc::Sender<LogEvent>>,
+        options: WorkerOptions,
+    ) -> Result<Self, AnyError> {
+        script.env = script.env.as_deref().map(env_json).transpose()?;
+
+        apply_v8_flags(&options.v8_flags)?;
+
+        // Wait for a slot before creating the isolate
+        let slot = match WORKER_SLOTS.get() {
+            Some(slots) => Some(slots.clone().acquire_owned().await?),
+            None => None,
+        };
+
+        let mut retry = 0;
+
+        loop {
+            let worker = Self::init(script.cheap_copy(), log_tx.clone(), options.clone()).await;
+
+            match worker {
+                Err(err) if retry < options.init_retry.max_retries && is_transient(&err) => {
+                    let delay = options.init_retry.delay(retry);
+                    retry += 1;
+
+                    log::warn!("worker initialization failed, retrying in {delay:?}: {err}");
+
+                    tokio::time::sleep(delay).await;
+                }
+                worker => {
+                    return worker.map(|worker| Self {
+                        _slot: slot,
+                        ..worker
+                    });
+                }
+            }
+        }
+    }
+
+    async fn init(
         script: Script,
         log_tx: Option<std::sync::mpsc::Sender<LogEvent>>,
+        options: WorkerOptions,
     ) -> Result<Self, AnyError> {
+        let create_params = options
+            .limits
+            .heap_max_mb
+            .map(|mb| v8::CreateParams::default().heap_limits(0, mb * 1024 * 1024));
+
+        let loaded_modules = LoadedModules::default();
+
+        let module_loader = Rc::new(RecordingModuleLoader::new(
+            options
+                .module_loader
+                .clone()
+                .unwrap_or_else(|| Rc::new(deno_core::FsModuleLoader)),
+            loaded_modules.clone(),
+            options.source_transform.clone(),
+        ));
+
         let mut js_runtime = match runtime_snapshot() {
             None => {
                 debug!("no runtime snapshot");
                 JsRuntime::new(deno_core::RuntimeOptions {
                     is_main: true,
-                    extensions: extensions(false),
-                    module_loader: Some(Rc::new(deno_core::FsModuleLoader)),
+                    inspector: options.inspector.is_some(),
+                    extensions: extensions(false, options.random_seed, options.blob_store.clone()),
+                    module_loader: Some(module_loader.clone()),
                     startup_snapshot: None,
+                    create_params,
                     ..Default::default()
                 })
             }
@@ -104,9 +855,11 @@ impl Worker {
                 debug!("using runtime snapshot");
                 JsRuntime::new(deno_core::RuntimeOptions {
                     is_main: true,
-                    extensions: extensions(true),
-                    module_loader: Some(Rc::new(deno_core::FsModuleLoader)),
+                    inspector: options.inspector.is_some(),
+                    extensions: extensions(true, options.random_seed, options.blob_store.clone()),
+                    module_loader: Some(module_loader),
                     startup_snapshot: Some(snapshot),
+                    create_params,
                     ..Default::default()
                 })
             }
@@ -114,8 +867,26 @@ impl Worker {
 
         debug!("runtime created, bootstrapping...");
 
+        options.microtask_policy.apply(&mut js_runtime);
+
+        let init_timeout = options.limits.init_timeout_ms.map(Duration::from_millis);
+
+        let init_watchdog = init_timeout.map(|timeout| {
+            TerminationWatchdog::new(js_runtime.v8_isolate().thread_safe_handle(), timeout)
+        });
+
+        let memory_pressure = Rc::new(Cell::new(false));
+
+        if options.limits.heap_max_mb.is_some() {
+            let grace = options.limits.heap_grace_mb.map(|mb| mb * 1024 * 1024);
+
+            watch_heap_limit(&mut js_runtime, memory_pressure.clone(), grace);
+        }
+
         let trigger_fetch;
         let trigger_scheduled;
+        let trigger_reset;
+        let trigger_listeners;
 
         // Log event sender
         {
@@ -128,78 +899,953 @@ impl Worker {
                     log::warn!("no log event sender provided");
                 }
             };
+
+            js_runtime
+                .op_state()
+                .borrow_mut()
+                .put::<LogLevel>(options.min_log_level);
+
+            if let Some(log_flush) = options.log_flush.clone() {
+                js_runtime
+                    .op_state()
+                    .borrow_mut()
+                    .put::<LogFlush>(log_flush);
+            }
+
+            js_runtime
+                .op_state()
+                .borrow_mut()
+                .put::<LogEventCount>(LogEventCount {
+                    max: options.limits.max_log_events,
+                    count: 0,
+                });
+        }
+
+        let config = BootstrapConfig::new(&options);
+
+        // Response checks
+        {
+            let op_state = js_runtime.op_state();
+            let mut op_state = op_state.borrow_mut();
+            op_state.put::<InvalidUtf8Policy>(options.invalid_utf8_policy);
+            op_state.put::<ContentLengthPolicy>(options.content_length_policy);
+            op_state.put::<RuntimeLimits>(options.limits.clone());
+        }
+
+        // Permissions
+        {
+            js_runtime
+                .op_state()
+                .borrow_mut()
+                .put::<Permissions>(Permissions {
+                    net: options.features.fetch,
+                });
+        }
+
+        // Outbound fetch client, used by deno_fetch instead of its default one
+        if let Some(config) = &options.fetch_client {
+            let client = config.create_client()?;
+            js_runtime.op_state().borrow_mut().put(client);
+        }
+
+        // Scheduled tasks
+        {
+            let op_state = js_runtime.op_state();
+            let mut op_state = op_state.borrow_mut();
+            op_state.put::<ScheduledOverlapPolicy>(options.scheduled_overlap);
+            op_state.put::<ScheduledRunning>(ScheduledRunning::default());
+        }
+
+        // File responses
+        {
+            if let Some(root) = options.file_response_root.clone() {
+                js_runtime
+                    .op_state()
+                    .borrow_mut()
+                    .put::<FileResponseRoot>(FileResponseRoot(root));
+            }
+        }
+
+        // Fetch interceptor
+        {
+            if let Some(interceptor) = options.fetch_interceptor {
+                js_runtime
+                    .op_state()
+                    .borrow_mut()
+                    .put::<FetchInterceptor>(interceptor);
+            }
+        }
+
+        // Bindings
+        {
+            js_runtime
+                .op_state()
+                .borrow_mut()
+                .put::<Bindings>(Bindings(options.bindings.clone()));
+        }
+
+        // Outbound header policy
+        {
+            if let Some(policy) = options.outbound_headers.clone() {
+                js_runtime
+                    .op_state()
+                    .borrow_mut()
+                    .put::<OutboundHeaderPolicy>(policy);
+            }
+        }
+
+        // Deadline header
+        {
+            if let Some(name) = &options.deadline_header {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|err| type_error(format!("Invalid deadline header: {err}")))?;
+
+                js_runtime
+                    .op_state()
+                    .borrow_mut()
+                    .put::<DeadlineHeader>(DeadlineHeader(name));
+            }
+        }
+
+        // Egress audit
+        {
+            if let Some(tx) = options.egress_audit.clone() {
+                js_runtime.op_state().borrow_mut().put::<EgressSender>(tx);
+            }
+        }
+
+        // KV store
+        {
+            if let Some(kv) = options.kv {
+                js_runtime
+                    .op_state()
+                    .borrow_mut()
+                    .put::<Arc<dyn KvStore>>(kv);
+            }
         }
 
         // Bootstrap
         {
             let script = format!(
-                "globalThis.bootstrap('{}', {})",
-                user_agent(),
-                script.env.unwrap_or("undefined".to_string())
+                "globalThis.bootstrap({}, {}, {})",
+                deno_core::serde_json::to_string(&user_agent())?,
+                script.env.unwrap_or("undefined".to_string()),
+                deno_core::serde_json::to_string(&config)?
             );
             let script = deno_core::ModuleCodeString::from(script);
 
-            match js_runtime.execute_script(deno_core::located_script_name!(), script) {
-                Ok(triggers) => {
-                    let scope = &mut js_runtime.handle_scope();
+            let bootstrap_timeout = options
+                .limits
+                .bootstrap_timeout_ms
+                .map(Duration::from_millis);
+
+            let bootstrap_watchdog = bootstrap_timeout.map(|timeout| {
+                TerminationWatchdog::new(js_runtime.v8_isolate().thread_safe_handle(), timeout)
+            });
+
+            let result = js_runtime.execute_script(deno_core::located_script_name!(), script);
+
+            // Cancelled before checking, it can't fire once checked. Fired even after
+            // the script completed, the pending termination leaves the isolate unusable.
+            let bootstrap_fired = bootstrap_watchdog.is_some_and(|w| !w.cancel());
+
+            let timed_out = match bootstrap_fired {
+                true => bootstrap_timeout,
+                false => init_watchdog
+                    .as_ref()
+                    .filter(|w| w.fired())
+                    .and(init_timeout),
+            };
+
+            if let Some(timeout) = timed_out {
+                return Err(InitTimeoutError(timeout).into());
+            }
+
+            let triggers = result?;
+
+            let scope = &mut js_runtime.handle_scope();
 
-                    let triggers = v8::Local::new(scope, triggers);
+            let triggers = v8::Local::new(scope, triggers);
 
-                    debug!("bootstrap succeeded with triggers: {:?}", triggers);
+            debug!("bootstrap succeeded with triggers: {:?}", triggers);
 
-                    let object: v8::Local<v8::Object> = match triggers.try_into() {
-                        Ok(object) => object,
-                        Err(err) => panic!("failed to convert triggers to object: {:?}", err),
-                    };
+            let object: v8::Local<v8::Object> = match triggers.try_into() {
+                Ok(object) => object,
+                Err(err) => {
+                    return Err(generic_error(format!(
+                        "failed to convert triggers to object: {err}"
+                    )))
+                }
+            };
+
+            trigger_fetch = crate::util::extract_trigger("fetch", scope, object)?;
+            trigger_scheduled = crate::util::extract_trigger("scheduled", scope, object)?;
+            trigger_reset = crate::util::extract_trigger("reset", scope, object)?;
+            trigger_listeners = crate::util::extract_trigger("listeners", scope, object)?;
+        };
 
-                    trigger_fetch = crate::util::extract_trigger("fetch", scope, object)
-                        .expect("fetch trigger not found");
-                    trigger_scheduled = crate::util::extract_trigger("scheduled", scope, object)
-                        .expect("scheduled trigger not found");
+        // Inspector
+        let wait_for_inspector = match options.inspector {
+            Some(ref inspector) => {
+                let inspector_rc = js_runtime.inspector();
+                let mut inspector_ref = inspector_rc.borrow_mut();
+
+                (inspector.register)(inspector_ref.get_session_sender());
+
+                if inspector.wait_for_session {
+                    debug!("waiting for a debugger to attach...");
+                    inspector_ref.wait_for_session();
                 }
-                Err(err) => panic!("bootstrap failed: {:?}", err),
+
+                inspector.wait_for_session
             }
+            None => false,
         };
 
         debug!("runtime bootstrapped, evaluating main module...");
 
         // Eval main module
         {
-            let mod_id = js_runtime
-                .load_main_module(&script.specifier, script.code)
-                .await?;
+            // Main module source given inline doesn't go through the loader
+            if script.code.is_some() {
+                loaded_modules.borrow_mut().push(script.specifier.clone());
+            }
 
-            let result = js_runtime.mod_evaluate(mod_id);
+            let code = transform_inline(
+                options.source_transform.as_ref(),
+                &script.specifier,
+                script.code,
+            )?;
 
-            let opts = deno_core::PollEventLoopOptions {
-                wait_for_inspector: false,
-                pump_v8_message_loop: true,
-            };
+            let eval = async {
+                let mod_id = js_runtime.load_main_module(&script.specifier, code).await?;
+
+                let result = js_runtime.mod_evaluate(mod_id);
 
-            js_runtime.run_event_loop(opts).await?;
+                let opts = deno_core::PollEventLoopOptions {
+                    wait_for_inspector,
+                    pump_v8_message_loop: true,
+                };
 
-            result.await?;
+                js_runtime.run_event_loop(opts).await?;
+
+                result.await
+            };
+
+            match (init_timeout, init_watchdog) {
+                (Some(timeout), Some(watchdog)) => {
+                    match tokio::time::timeout_at(watchdog.deadline, eval).await {
+                        // Fired after the module evaluated, before it was cancelled
+                        Ok(Ok(())) if !watchdog.cancel() => {
+                            return Err(InitTimeoutError(timeout).into())
+                        }
+                        Ok(Ok(())) => {}
+                        Ok(Err(_)) if watchdog.fired() => {
+                            return Err(InitTimeoutError(timeout).into())
+                        }
+                        Ok(Err(err)) => return Err(err),
+                        Err(_) => return Err(InitTimeoutError(timeout).into()),
+                    }
+                }
+                _ => eval.await?,
+            }
         };
 
         debug!("main module evaluated");
 
-        Ok(Self {
+        let mut worker = Self {
             js_runtime,
             trigger_fetch,
             trigger_scheduled,
+            trigger_reset,
+            trigger_listeners,
+            listeners: vec![],
+            reloads: 0,
+            memory_pressure,
+            idle_timeout: options.limits.idle_timeout_ms.map(Duration::from_millis),
+            headers_timeout: options.limits.headers_timeout_ms.map(Duration::from_millis),
+            last_exec: Rc::new(Cell::new(Instant::now())),
+            loaded_modules,
+            microtask_policy: options.microtask_policy,
+            wall_time: Duration::ZERO,
+            wall_time_budget: options
+                .limits
+                .lifetime_wall_time_ms
+                .map(Duration::from_millis),
+            static_routes: options.static_routes,
+            default_response: options.default_response,
+            url_normalization: options.url_normalization,
+            metrics: options.metrics,
+            wait_for_inspector,
+            cpu_soft_limit: options.limits.cpu_soft_limit_ms.map(Duration::from_millis),
+            scheduled_cpu_soft_limit: options
+                .limits
+                .scheduled_cpu_soft_limit_ms
+                .map(Duration::from_millis),
+            on_cpu_soft_limit: options.on_cpu_soft_limit,
+            source_transform: options.source_transform,
+            _slot: None,
+        };
+
+        worker.listeners = worker.list_listeners();
+
+        Ok(worker)
+    }
+
+    /// Whether the heap came close to `RuntimeLimits::heap_max_mb`.
+    ///
+    /// Once set, the flag stays set: hosts should stop sending work
+    /// to this worker and recycle it.
+    pub fn memory_pressure(&self) -> bool {
+        self.memory_pressure.get()
+    }
+
+    /// Specifiers of the modules loaded so far, main module first.
+    ///
+    /// Modules are listed once loaded, dynamic imports included.
+    pub fn loaded_modules(&self) -> Vec<deno_core::ModuleSpecifier> {
+        self.loaded_modules.borrow().clone()
+    }
+
+    /// Current value of `globalThis.env` as JSON, including the worker's own changes.
+    ///
+    /// Values without a JSON representation are serialized as `JSON.stringify`
+    /// does (e.g. bindings become empty objects), `null` if `env` is unset.
+    pub fn read_env(&mut self) -> Result<deno_core::serde_json::Value, AnyError> {
+        let scope = &mut self.js_runtime.handle_scope();
+        let scope = &mut v8::TryCatch::new(scope);
+
+        let global = scope.get_current_context().global(scope);
+        let key = v8::String::new(scope, "env").unwrap().into();
+
+        let env = match global.get(scope, key) {
+            Some(env) if !env.is_null_or_undefined() => env,
+            _ => return Ok(deno_core::serde_json::Value::Null),
+        };
+
+        let json = match v8::json::stringify(scope, env) {
+            Some(json) => json.to_rust_string_lossy(scope),
+            None => {
+                let message = match scope.exception() {
+                    Some(exception) => exception.to_rust_string_lossy(scope),
+                    None => "unknown error".to_string(),
+                };
+
+                return Err(generic_error(format!("failed to serialize env: {message}")));
+            }
+        };
+
+        Ok(deno_core::serde_json::from_str(&json)?)
+    }
+
+    /// Write a V8 heap snapshot of the isolate, loadable in the DevTools memory panel
+    /// (use a `.heapsnapshot` extension).
+    ///
+    /// Meant for diagnosing leaks between `exec` calls: it blocks the worker while the
+    /// whole heap is serialized, which can take a while for large heaps.
+    pub fn write_heap_snapshot(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let mut result = Ok(());
+
+        self.js_runtime
+            .v8_isolate()
+            .take_heap_snapshot(|chunk| match file.write_all(chunk) {
+                Ok(()) => true,
+                Err(err) => {
+                    result = Err(err);
+                    false
+                }
+            });
+
+        result?;
+        file.flush()
+    }
+
+    /// Task types the worker registered an event listener for, e.g. only
+    /// `TaskType::Scheduled` for a cron-only worker.
+    ///
+    /// Listeners are registered by the main module, so a `reload` may change them.
+    pub fn describe(&self) -> Vec<TaskType> {
+        self.listeners.clone()
+    }
+
+    /// Ask the js realm which listeners are registered, after the main module ran.
+    fn list_listeners(&mut self) -> Vec<TaskType> {
+        let scope = &mut self.js_runtime.handle_scope();
+        let listeners = v8::Local::new(scope, &self.trigger_listeners);
+        let recv = v8::undefined(scope);
+
+        let types = match listeners.call(scope, recv.into(), &[]) {
+            Some(types) => types,
+            None => {
+                log::warn!("failed to list event listeners");
+                return vec![];
+            }
+        };
+
+        deno_core::serde_v8::from_v8(scope, types).unwrap_or_else(|err| {
+            log::warn!("failed to convert event listeners: {err}");
+            vec![]
         })
     }
 
+    /// Evaluate a new main module in the existing isolate, for development hot reload.
+    ///
+    /// Event listeners of the previous script are cleared first, and `env` is replaced
+    /// by `script.env` if given (with the `KV` binding attached again), kept otherwise.
+    /// The new main module and the modules it imports are evaluated as fresh instances,
+    /// the state of the previous ones is discarded. Other side effects of the previous
+    /// script (globals) are kept, the options given to `Worker::new` still apply.
+    pub async fn reload(&mut self, script: Script) -> Result<(), AnyError> {
+        let env = script.env.as_deref().map(env_json).transpose()?;
+
+        {
+            let scope = &mut self.js_runtime.handle_scope();
+            let reset = v8::Local::new(scope, &self.trigger_reset);
+            let recv = v8::undefined(scope);
+
+            let mut args = vec![];
+
+            if let Some(env) = env {
+                let json = v8::String::new(scope, &env)
+                    .ok_or_else(|| generic_error("failed to allocate env"))?;
+                let env = v8::json::parse(scope, json)
+                    .ok_or_else(|| generic_error("failed to parse env"))?;
+
+                args.push(env);
+            }
+
+            if reset.call(scope, recv.into(), &args).is_none() {
+                return Err(generic_error("failed to reset event listeners"));
+            }
+        }
+
+        self.listeners.clear();
+
+        // A specifier can only be loaded once, the query makes each reload unique
+        self.reloads += 1;
+
+        let mut specifier = script.specifier;
+        specifier.set_query(Some(&format!("reload={}", self.reloads)));
+
+        if script.code.is_some() {
+            self.loaded_modules.borrow_mut().push(specifier.clone());
+        }
+
+        let code = transform_inline(self.source_transform.as_ref(), &specifier, script.code)?;
+
+        let mod_id = self.js_runtime.load_side_module(&specifier, code).await?;
+
+        let result = self.js_runtime.mod_evaluate(mod_id);
+
+        let opts = deno_core::PollEventLoopOptions {
+            wait_for_inspector: self.wait_for_inspector,
+            pump_v8_message_loop: true,
+        };
+
+        let result = match self.js_runtime.run_event_loop(opts).await {
+            Ok(()) => result.await,
+            Err(err) => Err(err),
+        };
+
+        // Listeners registered before a failure are registered all the same
+        self.listeners = self.list_listeners();
+
+        result
+    }
+
+    /// Drain the streamed responses still in flight, then drop the worker.
+    ///
+    /// Streams are left open when an `exec` future is dropped before completion: the
+    /// event loop runs until every stream ended (chunks already enqueued reach the
+    /// host) or `deadline` elapsed, then the isolate is torn down. Consuming the
+    /// worker, no task can be started meanwhile.
+    pub async fn shutdown(mut self, deadline: Duration) -> Result<(), AnyError> {
+        let opts = deno_core::PollEventLoopOptions {
+            wait_for_inspector: false,
+            pump_v8_message_loop: true,
+        };
+
+        let drain = std::future::poll_fn(|cx| {
+            if open_streams(&self.js_runtime.op_state().borrow()) == 0 {
+                return std::task::Poll::Ready(Ok(()));
+            }
+
+            match self.js_runtime.poll_event_loop(cx, opts) {
+                std::task::Poll::Pending
+                    if open_streams(&self.js_runtime.op_state().borrow()) == 0 =>
+                {
+                    std::task::Poll::Ready(Ok(()))
+                }
+                poll => poll,
+            }
+        });
+
+        match tokio::time::timeout(deadline, drain).await {
+            Ok(result) => result,
+            Err(_) => {
+                let open = open_streams(&self.js_runtime.op_state().borrow());
+                log::warn!("shutdown deadline reached, closing {open} response streams");
+                Ok(())
+            }
+        }
+    }
+
+    /// Resolves once the worker went `RuntimeLimits::idle_timeout_ms` without `exec`.
+    ///
+    /// The future doesn't borrow the worker, so hosts can race it against
+    /// incoming tasks and drop the worker when it resolves. It never resolves
+    /// if no idle timeout is configured.
+    pub fn idle_watch(&self) -> impl std::future::Future<Output = ()> {
+        let idle_timeout = self.idle_timeout;
+        let last_exec = self.last_exec.clone();
+
+        async move {
+            let idle_timeout = match idle_timeout {
+                Some(idle_timeout) => idle_timeout,
+                None => return std::future::pending().await,
+            };
+
+            loop {
+                let deadline = last_exec.get() + idle_timeout;
+
+                if Instant::now() >= deadline {
+                    return;
+                }
+
+                tokio::time::sleep_until(deadline).await;
+            }
+        }
+    }
+
+    /// Dispatch a task to the worker and run its event loop until the task settled.
+    ///
+    /// `Ok(())` is the only success: the task ran and the worker's pending work
+    /// completed (a fetch task that didn't respond gets the default response).
+    /// Any failure is an `Err`, there is no success reason. If the task carries an
+    /// error channel (`with_error_tx`), the reason of the failure is also sent on
+    /// it, and on success the sender is dropped without a value:
+    ///
+    /// - `HeadersTimeoutError` is sent as `TerminationReason::HeadersTimeout`
+    /// - `PayloadTooLargeError` as `TerminationReason::PayloadTooLarge`
+    /// - `TerminatedError` as `TerminationReason::MemoryLimit` if the worker was under
+    ///   memory pressure when terminated, as `TerminationReason::Terminated` otherwise
+    /// - a JS exception as `TerminationReason::Exception`, or `PassThrough`
+    ///   if the worker called `event.passThroughOnException()` and didn't respond
+    /// - any other error (refused task, runtime failure) as `TerminationReason::Error`
     pub async fn exec(&mut self, mut task: Task) -> Result<(), AnyError> {
+        let error_tx = task.take_error_tx();
+
+        // Reset before any early return, a stale flag would forward a refused task
+        {
+            let op_state = self.js_runtime.op_state();
+            let mut op_state = op_state.borrow_mut();
+            op_state.put::<PassThrough>(PassThrough::default());
+            op_state.put::<ResponseStats>(ResponseStats::default());
+        }
+
+        let result = self.exec_task(task).await;
+
+        if let (Err(err), Some(error_tx)) = (&result, error_tx) {
+            // The host may not care about the reason anymore
+            let _ = error_tx.send(self.termination_reason(err));
+        }
+
+        result
+    }
+
+    async fn exec_task(&mut self, mut task: Task) -> Result<(), AnyError> {
         debug!("executing task {:?}", task.task_type());
 
-        crate::util::exec_task(self, &mut task);
+        if let Task::Fetch(Some(init)) = &mut task {
+            let uri = self.url_normalization.apply(init.req.uri());
+            *init.req.uri_mut() = uri;
+        }
+
+        // Static routes bypass js realm entirely
+        if let Task::Fetch(Some(init)) = &task {
+            let route = self.static_routes.iter().find(|r| r.matches(init.path()));
+
+            if let Some(route) = route {
+                debug!("serving static route {} for {}", route.path, init.path());
+
+                let res = route.response()?;
+
+                if let Task::Fetch(Some(init)) = task {
+                    init.respond(res);
+                }
+
+                return Ok(());
+            }
+        }
+
+        // Refused before the body is handed to the js realm
+        if let Task::Fetch(Some(init)) = &task {
+            let max = self
+                .js_runtime
+                .op_state()
+                .borrow()
+                .try_borrow::<RuntimeLimits>()
+                .and_then(|limits| limits.max_request_bytes);
+
+            let len = init.req.body().len();
+
+            if let Some(max) = max.filter(|max| len > *max) {
+                return Err(PayloadTooLargeError { len, max }.into());
+            }
+        }
+
+        if let Some(budget) = self.wall_time_budget {
+            if self.wall_time >= budget {
+                return Err(generic_error(format!(
+                    "worker wall time budget of {budget:?} exhausted"
+                )));
+            }
+        }
+
+        // Refused before any accounting, the worker may only handle other task types
+        let listener = match task.task_type() {
+            TaskType::Fetch => "fetch",
+            TaskType::Scheduled => "scheduled",
+        };
+
+        if !self.listeners.contains(&task.task_type()) {
+            return Err(generic_error(format!(
+                "no {listener} event listener registered"
+            )));
+        }
+
+        let start = Instant::now();
+
+        self.last_exec.set(start);
+
+        self.microtask_policy.apply(&mut self.js_runtime);
 
         let opts = deno_core::PollEventLoopOptions {
-            wait_for_inspector: false,
+            wait_for_inspector: self.wait_for_inspector,
             pump_v8_message_loop: true,
         };
 
-        self.js_runtime.run_event_loop(opts).await
+        if let Task::Scheduled(_) = task {
+            let (policy, running) = {
+                let op_state = self.js_runtime.op_state();
+                let op_state = op_state.borrow();
+                (
+                    *op_state.borrow::<ScheduledOverlapPolicy>(),
+                    scheduled_running(&op_state),
+                )
+            };
+
+            if running > 0 {
+                match policy {
+                    ScheduledOverlapPolicy::Allow => {}
+                    ScheduledOverlapPolicy::Queue => {
+                        debug!("{running} scheduled task(s) still running, waiting");
+
+                        // Not the whole event loop, an interval would keep it alive forever
+                        std::future::poll_fn(|cx| {
+                            match self.js_runtime.poll_event_loop(cx, opts) {
+                                std::task::Poll::Pending
+                                    if scheduled_running(&self.js_runtime.op_state().borrow())
+                                        == 0 =>
+                                {
+                                    std::task::Poll::Ready(Ok(()))
+                                }
+                                poll => poll,
+                            }
+                        })
+                        .await?;
+                    }
+                    ScheduledOverlapPolicy::Reject => {
+                        return Err(generic_error(format!(
+                            "{running} scheduled task(s) still running"
+                        )));
+                    }
+                }
+            }
+        }
+
+        let cpu_start = crate::util::thread_cpu_time();
+
+        {
+            let op_state = self.js_runtime.op_state();
+            let mut op_state = op_state.borrow_mut();
+
+            op_state.put::<CpuTimeStart>(CpuTimeStart(cpu_start));
+
+            op_state.put::<TaskDeadline>(TaskDeadline {
+                headers: match task.task_type() {
+                    TaskType::Fetch => self.headers_timeout.map(|timeout| start + timeout),
+                    TaskType::Scheduled => None,
+                },
+                wall_time: self
+                    .wall_time_budget
+                    .map(|budget| start + budget.saturating_sub(self.wall_time)),
+            });
+
+            if let Some(events) = op_state.try_borrow_mut::<LogEventCount>() {
+                events.count = 0;
+            }
+
+            op_state.put::<SubrequestCount>(SubrequestCount::default());
+        }
+
+        let task_type = task.task_type();
+
+        let rid = crate::util::add_task(self, &mut task)?;
+
+        let result = match crate::util::exec_task(self, &task, rid) {
+            Ok(()) => self.run_task(task_type, start, cpu_start, opts).await,
+            Err(err) => Err(err),
+        };
+
+        match task_type {
+            TaskType::Fetch => self.release_fetch(rid, &result),
+            TaskType::Scheduled => {
+                release_scheduled(&mut self.js_runtime.op_state().borrow_mut(), rid)
+            }
+        }
+
+        if let Some(metrics) = &self.metrics {
+            let cpu_ms = crate::util::thread_cpu_time()
+                .saturating_sub(cpu_start)
+                .as_millis() as u64;
+            let wall_ms = start.elapsed().as_millis() as u64;
+
+            let event = match task_type {
+                TaskType::Fetch => {
+                    let stats = self
+                        .js_runtime
+                        .op_state()
+                        .borrow()
+                        .try_borrow::<ResponseStats>()
+                        .copied()
+                        .unwrap_or_default();
+
+                    MetricEvent::RequestCompleted {
+                        cpu_ms,
+                        wall_ms,
+                        status: stats.status,
+                        bytes_in: stats.bytes_in,
+                        bytes_out: stats.bytes_out,
+                        response_started: stats.started,
+                        init_us: stats.init_time.as_micros() as u64,
+                    }
+                }
+                TaskType::Scheduled => MetricEvent::ScheduledCompleted { cpu_ms, wall_ms },
+            };
+
+            if metrics.tx.send(event).is_err() {
+                log::warn!("failed to send task metrics");
+            }
+        }
+
+        let end = Instant::now();
+
+        self.wall_time += end - start;
+        self.last_exec.set(end);
+
+        result
+    }
+
+    /// Run the event loop of a triggered task until it completes.
+    async fn run_task(
+        &mut self,
+        task_type: TaskType,
+        start: Instant,
+        cpu_start: Duration,
+        opts: deno_core::PollEventLoopOptions,
+    ) -> Result<(), AnyError> {
+        let mut samples = match self.metrics.clone() {
+            Some(MetricsOptions {
+                tx,
+                interval: Some(interval),
+            }) => Some((tx, tokio::time::interval_at(start + interval, interval))),
+            _ => None,
+        };
+
+        let cpu_soft_limit = match task_type {
+            TaskType::Fetch => self.cpu_soft_limit,
+            TaskType::Scheduled => self.scheduled_cpu_soft_limit.or(self.cpu_soft_limit),
+        };
+
+        let mut cpu_soft_limit =
+            cpu_soft_limit
+                .zip(self.on_cpu_soft_limit.clone())
+                .map(|(limit, callback)| {
+                    let interval = tokio::time::interval(CPU_SOFT_LIMIT_CHECK_INTERVAL);
+                    (limit, callback, interval)
+                });
+
+        let mut headers_timeout = match task_type {
+            TaskType::Fetch => self.headers_timeout.map(|timeout| {
+                let sleep = Box::pin(tokio::time::sleep_until(start + timeout));
+                (timeout, sleep)
+            }),
+            TaskType::Scheduled => None,
+        };
+
+        match (&samples, &cpu_soft_limit, &headers_timeout) {
+            (None, None, None) => self.js_runtime.run_event_loop(opts).await,
+            _ => {
+                std::future::poll_fn(|cx| {
+                    if let Some((timeout, sleep)) = &mut headers_timeout {
+                        if sleep.as_mut().poll(cx).is_ready() {
+                            if !self.response_started() {
+                                let err = HeadersTimeoutError(*timeout);
+                                return std::task::Poll::Ready(Err(err.into()));
+                            }
+
+                            headers_timeout = None;
+                        }
+                    }
+
+                    if let Some((tx, interval)) = &mut samples {
+                        while interval.poll_tick(cx).is_ready() {
+                            let sample = self.sample_metrics(start, cpu_start);
+
+                            if tx.send(MetricEvent::Sample(sample)).is_err() {
+                                log::warn!("failed to send task metrics");
+                            }
+                        }
+                    }
+
+                    if let Some((limit, callback, interval)) = &mut cpu_soft_limit {
+                        while interval.poll_tick(cx).is_ready() {}
+
+                        let cpu_time = crate::util::thread_cpu_time().saturating_sub(cpu_start);
+
+                        // Fired once per task
+                        if cpu_time >= *limit {
+                            callback(cpu_time);
+                            cpu_soft_limit = None;
+                        }
+                    }
+
+                    self.js_runtime.poll_event_loop(cx, opts)
+                })
+                .await
+            }
+        }
+    }
+
+    /// Release the response sender of a fetch task, whatever its outcome.
+    ///
+    /// A worker that completed or failed without responding gets the default
+    /// response sent on its behalf. The sender is dropped without a response
+    /// if the host forwards the request (pass through) or the task was stopped
+    /// by a limit, the host answers with the error returned by `exec`.
+    fn release_fetch(&mut self, rid: ResourceId, result: &Result<(), AnyError>) {
+        let stopped = match result {
+            Ok(()) => false,
+            Err(err) => {
+                err.downcast_ref::<HeadersTimeoutError>().is_some()
+                    || err.downcast_ref::<TerminatedError>().is_some()
+                    || matches!(
+                        self.termination_reason(err),
+                        TerminationReason::PassThrough(_)
+                    )
+            }
+        };
+
+        let res = match stopped {
+            true => None,
+            false => match self.default_response.response() {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    log::error!("invalid default response: {err}");
+                    None
+                }
+            },
+        };
+
+        let op_state = self.js_runtime.op_state();
+        let mut op_state = op_state.borrow_mut();
+
+        // Not taken if the trigger failed before creating the event
+        let _ = op_state.resource_table.take_any(rid);
+
+        if respond_default(&mut op_state, res) {
+            log::warn!("worker did not respond, sent the default response");
+        }
+    }
+
+    /// Whether the current fetch task sent its response head.
+    fn response_started(&mut self) -> bool {
+        self.js_runtime
+            .op_state()
+            .borrow()
+            .try_borrow::<ResponseStats>()
+            .is_some_and(|stats| stats.started)
+    }
+
+    fn termination_reason(&mut self, err: &AnyError) -> TerminationReason {
+        if err.downcast_ref::<HeadersTimeoutError>().is_some() {
+            return TerminationReason::HeadersTimeout;
+        }
+
+        // The heap limit callback only terminates a worker already under pressure
+        if err.downcast_ref::<TerminatedError>().is_some() {
+            return match self.memory_pressure() {
+                true => TerminationReason::MemoryLimit,
+                false => TerminationReason::Terminated,
+            };
+        }
+
+        if err.downcast_ref::<PayloadTooLargeError>().is_some() {
+            return TerminationReason::PayloadTooLarge;
+        }
+
+        let pass_through = self
+            .js_runtime
+            .op_state()
+            .borrow()
+            .try_borrow::<PassThrough>()
+            .is_some_and(|p| p.0);
+
+        // Too late to forward the request once a response was sent
+        match pass_through && !self.response_started() {
+            true => TerminationReason::PassThrough(err.to_string()),
+            false => err.into(),
+        }
+    }
+
+    fn sample_metrics(&mut self, start: Instant, cpu_start: Duration) -> TaskMetrics {
+        let mut heap = v8::HeapStatistics::default();
+        self.js_runtime.v8_isolate().get_heap_statistics(&mut heap);
+
+        TaskMetrics {
+            cpu_time: crate::util::thread_cpu_time().saturating_sub(cpu_start),
+            wall_time: start.elapsed(),
+            heap_used: heap.used_heap_size(),
+        }
+    }
+
+    /// Outbound requests of the last task executed, redirects and intercepted requests included.
+    pub(crate) fn subrequests(&mut self) -> u64 {
+        self.js_runtime
+            .op_state()
+            .borrow()
+            .try_borrow::<SubrequestCount>()
+            .map_or(0, |count| count.0)
+    }
+
+    /// Used V8 heap, in bytes.
+    pub(crate) fn heap_used(&mut self) -> usize {
+        let mut heap = v8::HeapStatistics::default();
+        self.js_runtime.v8_isolate().get_heap_statistics(&mut heap);
+
+        heap.used_heap_size()
+    }
+
+    /// Wall-clock time spent in `exec` over the worker lifetime.
+    pub fn wall_time(&self) -> Duration {
+        self.wall_time
+    }
+
+    /// Whether `RuntimeLimits::lifetime_wall_time_ms` is used up, further tasks are refused.
+    pub fn wall_time_exhausted(&self) -> bool {
+        self.wall_time_budget
+            .is_some_and(|budget| self.wall_time >= budget)
     }
 }
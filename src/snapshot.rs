@@ -22,7 +22,7 @@ pub fn create_runtime_snapshot() {
     let options: CreateSnapshotOptions<File> = CreateSnapshotOptions {
         cargo_manifest_dir: env!("CARGO_MANIFEST_DIR"),
         startup_snapshot: None,
-        extensions: extensions(false),
+        extensions: extensions(false, None, None),
         skip_op_registration: false,
         serializer: Box::new(serializer),
         with_runtime_cb: None,
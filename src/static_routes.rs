@@ -0,0 +1,98 @@
+use bytes::Bytes;
+
+use crate::ResponseBody;
+
+/// Response served by the host for a path, without dispatching to the worker.
+///
+/// Useful for health checks and other fixed responses.
+#[derive(Debug, Clone)]
+pub struct StaticRoute {
+    /// Request path, a trailing `*` matches any path starting with the prefix.
+    pub path: String,
+
+    pub status: u16,
+
+    pub headers: Vec<(String, String)>,
+
+    pub body: Bytes,
+}
+
+impl StaticRoute {
+    pub fn new(path: impl Into<String>, status: u16, body: impl Into<Bytes>) -> Self {
+        StaticRoute {
+            path: path.into(),
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        match self.path.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == self.path,
+        }
+    }
+
+    pub(crate) fn response(&self) -> Result<http_v02::Response<ResponseBody>, http_v02::Error> {
+        build_response(self.status, &self.headers, &self.body)
+    }
+}
+
+/// Response sent by the host when a fetch task completes without responding.
+#[derive(Debug, Clone)]
+pub struct DefaultResponse {
+    pub status: u16,
+
+    pub headers: Vec<(String, String)>,
+
+    pub body: Bytes,
+}
+
+impl Default for DefaultResponse {
+    fn default() -> Self {
+        DefaultResponse {
+            status: 500,
+            headers: Vec::new(),
+            body: Bytes::new(),
+        }
+    }
+}
+
+impl DefaultResponse {
+    pub fn new(status: u16, body: impl Into<Bytes>) -> Self {
+        DefaultResponse {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub(crate) fn response(&self) -> Result<http_v02::Response<ResponseBody>, http_v02::Error> {
+        build_response(self.status, &self.headers, &self.body)
+    }
+}
+
+fn build_response(
+    status: u16,
+    headers: &[(String, String)],
+    body: &Bytes,
+) -> Result<http_v02::Response<ResponseBody>, http_v02::Error> {
+    let mut builder = http_v02::Response::builder().status(status);
+
+    for (k, v) in headers {
+        builder = builder.header(k, v);
+    }
+
+    builder.body(ResponseBody::buffered(status, false, Some(body.clone())))
+}
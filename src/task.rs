@@ -1,7 +1,48 @@
+use deno_core::error::AnyError;
+use deno_core::error::JsError;
+use deno_core::serde::Deserialize;
+
 use crate::FetchInit;
 use crate::ScheduledInit;
 
-#[derive(Debug)]
+/// Why a task failed, sent on the error channel of the task if any.
+///
+/// Only failures have a reason: a successful `Worker::exec` returns `Ok(())`
+/// and sends nothing on the error channel.
+#[derive(Debug, Clone)]
+pub enum TerminationReason {
+    /// The worker threw an uncaught exception.
+    Exception(String),
+    /// The task was refused or the runtime failed.
+    Error(String),
+    /// The worker failed before responding after calling `event.passThroughOnException()`:
+    /// the host should forward the request to the origin instead of serving an error.
+    PassThrough(String),
+    /// The worker didn't respond within `RuntimeLimits::headers_timeout_ms`.
+    HeadersTimeout,
+    /// The request body exceeded `RuntimeLimits::max_request_bytes`, the host
+    /// should answer with a `413 Payload Too Large`.
+    PayloadTooLarge,
+    /// The execution was terminated on reaching `RuntimeLimits::heap_max_mb`
+    /// (the worker was under `memory_pressure` when it was terminated).
+    MemoryLimit,
+    /// The execution was forcibly terminated for another reason, not a worker exception.
+    Terminated,
+}
+
+impl From<&AnyError> for TerminationReason {
+    fn from(err: &AnyError) -> Self {
+        match err.downcast_ref::<JsError>() {
+            Some(err) => TerminationReason::Exception(err.exception_message.clone()),
+            None => TerminationReason::Error(err.to_string()),
+        }
+    }
+}
+
+pub type ErrorSender = tokio::sync::oneshot::Sender<TerminationReason>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TaskType {
     Fetch,
     Scheduled,
@@ -19,4 +60,14 @@ impl Task {
             Task::Scheduled(_) => TaskType::Scheduled,
         }
     }
+
+    /// Error channel of the task, `Worker::exec` sends the `TerminationReason`
+    /// on it when the task fails and drops it when the task succeeds.
+    pub(crate) fn take_error_tx(&mut self) -> Option<ErrorSender> {
+        match self {
+            Task::Fetch(Some(init)) => init.error_tx.take(),
+            Task::Scheduled(Some(init)) => init.error_tx.take(),
+            _ => None,
+        }
+    }
 }
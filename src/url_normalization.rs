@@ -0,0 +1,138 @@
+use http_v02::uri::Authority;
+use http_v02::uri::PathAndQuery;
+use http_v02::Uri;
+
+/// Trailing slash handling of request paths, the root path `/` is never changed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Leave paths as sent by the client.
+    #[default]
+    Keep,
+    /// Append a slash to paths without one, e.g. `/docs` becomes `/docs/`.
+    Add,
+    /// Remove the trailing slash, e.g. `/docs/` becomes `/docs`.
+    Remove,
+}
+
+/// Normalization of request URLs, applied by the host before static routes
+/// are matched and before the request reaches the worker.
+///
+/// Every step is disabled by default.
+#[derive(Debug, Default, Clone)]
+pub struct UrlNormalization {
+    /// Lowercase the host of absolute request URLs.
+    pub lowercase_host: bool,
+
+    /// Resolve `.` and `..` path segments, e.g. `/a/./b/../c` becomes `/a/c`.
+    pub resolve_dot_segments: bool,
+
+    pub trailing_slash: TrailingSlash,
+}
+
+impl UrlNormalization {
+    fn is_noop(&self) -> bool {
+        !self.lowercase_host
+            && !self.resolve_dot_segments
+            && self.trailing_slash == TrailingSlash::Keep
+    }
+
+    /// Normalize a request URI, returned unchanged if the result isn't a valid URI.
+    pub(crate) fn apply(&self, uri: &Uri) -> Uri {
+        if self.is_noop() {
+            return uri.clone();
+        }
+
+        let mut parts = uri.clone().into_parts();
+
+        if self.lowercase_host {
+            parts.authority = parts.authority.map(|authority| lowercase_host(&authority));
+        }
+
+        if let Some(path_and_query) = &parts.path_and_query {
+            let path = self.normalize_path(path_and_query.path());
+
+            let path_and_query = match path_and_query.query() {
+                Some(query) => format!("{path}?{query}"),
+                None => path,
+            };
+
+            match PathAndQuery::try_from(path_and_query) {
+                Ok(path_and_query) => parts.path_and_query = Some(path_and_query),
+                Err(err) => log::debug!("failed to normalize {uri}: {err}"),
+            }
+        }
+
+        match Uri::from_parts(parts) {
+            Ok(normalized) => normalized,
+            Err(err) => {
+                log::debug!("failed to normalize {uri}: {err}");
+                uri.clone()
+            }
+        }
+    }
+
+    fn normalize_path(&self, path: &str) -> String {
+        // Asterisk form (`OPTIONS *`)
+        if !path.starts_with('/') {
+            return path.to_string();
+        }
+
+        let mut path = match self.resolve_dot_segments {
+            true => remove_dot_segments(path),
+            false => path.to_string(),
+        };
+
+        match self.trailing_slash {
+            TrailingSlash::Keep => {}
+            TrailingSlash::Add => {
+                if !path.ends_with('/') {
+                    path.push('/');
+                }
+            }
+            TrailingSlash::Remove => {
+                while path.len() > 1 && path.ends_with('/') {
+                    path.pop();
+                }
+            }
+        }
+
+        path
+    }
+}
+
+/// Lowercase the host of an authority, leaving user info untouched.
+fn lowercase_host(authority: &Authority) -> Authority {
+    let lowercased = match authority.as_str().rsplit_once('@') {
+        Some((userinfo, host)) => format!("{userinfo}@{}", host.to_ascii_lowercase()),
+        None => authority.as_str().to_ascii_lowercase(),
+    };
+
+    Authority::try_from(lowercased).unwrap_or_else(|_| authority.clone())
+}
+
+/// Resolve the `.` and `..` segments of an absolute path (RFC 3986, section 5.2.4).
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+
+    let mut input = path.strip_prefix('/').unwrap_or(path).split('/').peekable();
+
+    while let Some(segment) = input.next() {
+        let last = input.peek().is_none();
+
+        match segment {
+            "." | ".." => {
+                if segment == ".." {
+                    segments.pop();
+                }
+
+                // `/a/b/..` resolves to the directory `/a/`
+                if last {
+                    segments.push("");
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    format!("/{}", segments.join("/"))
+}
@@ -1,39 +1,69 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use deno_core::error::generic_error;
+use deno_core::error::type_error;
+use deno_core::error::AnyError;
+use deno_core::error::JsError;
 use deno_core::v8;
+use deno_core::OpState;
+use deno_core::Resource;
+use deno_core::ResourceId;
 
 use crate::Task;
+use crate::TerminatedError;
 use crate::Worker;
 
 pub(crate) fn extract_trigger<'a>(
     name: &str,
     scope: &mut v8::HandleScope<'a>,
     object: v8::Local<'a, v8::Object>,
-) -> Option<v8::Global<v8::Function>> {
-    let key = v8::String::new(scope, name).unwrap().into();
+) -> Result<v8::Global<v8::Function>, AnyError> {
+    let not_found = || generic_error(format!("{name} trigger not found"));
+
+    let key = v8::String::new(scope, name).ok_or_else(not_found)?.into();
 
     let ret = match object.get(scope, key) {
         Some(fetch) => fetch,
-        None => return None,
+        None => return Err(not_found()),
     };
 
     let ret: v8::Local<v8::Function> = match ret.try_into() {
         Ok(ret) => ret,
-        Err(_) => return None,
+        Err(_) => return Err(not_found()),
     };
 
-    Some(v8::Global::new(scope, ret))
+    Ok(v8::Global::new(scope, ret))
 }
 
-pub(crate) fn exec_task(worker: &mut Worker, task: &mut Task) {
-    let rid = {
-        let op_state_rc = worker.js_runtime.op_state();
-        let mut op_state = op_state_rc.borrow_mut();
+/// Remove a resource from the table and take ownership of it.
+///
+/// Fails instead of panicking if the resource is still referenced elsewhere
+/// (e.g. by a pending async op).
+pub(crate) fn take_resource<T: Resource>(
+    state: &mut OpState,
+    rid: ResourceId,
+) -> Result<T, AnyError> {
+    let resource = state.resource_table.take::<T>(rid)?;
 
-        match task {
-            Task::Fetch(data) => op_state.resource_table.add(data.take().unwrap()),
-            Task::Scheduled(data) => op_state.resource_table.add(data.take().unwrap()),
-        }
+    Rc::try_unwrap(resource).map_err(|_| type_error(format!("Resource {rid} is in use")))
+}
+
+/// Hand the init of a task to the resource table, for its trigger to take.
+pub(crate) fn add_task(worker: &mut Worker, task: &mut Task) -> Result<ResourceId, AnyError> {
+    let op_state_rc = worker.js_runtime.op_state();
+    let mut op_state = op_state_rc.borrow_mut();
+
+    let rid = match task {
+        Task::Fetch(data) => data.take().map(|data| op_state.resource_table.add(data)),
+        Task::Scheduled(data) => data.take().map(|data| op_state.resource_table.add(data)),
     };
 
+    rid.ok_or_else(|| generic_error("task was already executed"))
+}
+
+/// Call the trigger of a task added with `add_task`.
+pub(crate) fn exec_task(worker: &mut Worker, task: &Task, rid: ResourceId) -> Result<(), AnyError> {
     let scope = &mut worker.js_runtime.handle_scope();
 
     let trigger = v8::Local::new(
@@ -48,8 +78,44 @@ pub(crate) fn exec_task(worker: &mut Worker, task: &mut Task) {
 
     let rid = v8::Integer::new(scope, rid as i32).into();
 
-    match trigger.call(scope, recv.into(), &[rid]) {
-        Some(_) => log::debug!("successfully called trigger"),
-        None => log::error!("failed to call trigger"),
+    let scope = &mut v8::TryCatch::new(scope);
+
+    if trigger.call(scope, recv.into(), &[rid]).is_some() {
+        log::debug!("successfully called trigger");
+        return Ok(());
+    }
+
+    // A forced termination also returns no value, but isn't a worker exception
+    if scope.has_terminated() {
+        log::warn!("execution terminated during trigger call");
+        return Err(TerminatedError.into());
+    }
+
+    match scope.exception() {
+        Some(exception) => Err(JsError::from_v8_exception(scope, exception).into()),
+        None => {
+            log::error!("failed to call trigger");
+            Err(generic_error("failed to call the event trigger"))
+        }
+    }
+}
+
+/// CPU time consumed by the current thread.
+#[cfg(unix)]
+pub(crate) fn thread_cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
     };
+
+    // SAFETY: `ts` is a valid timespec, CLOCK_THREAD_CPUTIME_ID is always supported on unix
+    match unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) } {
+        0 => Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32),
+        _ => Duration::ZERO,
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn thread_cpu_time() -> Duration {
+    Duration::ZERO
 }
@@ -0,0 +1,79 @@
+mod common;
+
+use std::sync::Arc;
+
+use deno_core::error::type_error;
+use deno_core::serde_json::json;
+use deno_core::serde_json::Value;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use openworkers_runtime::AnyError;
+use openworkers_runtime::Binding;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::worker;
+
+/// `ECHO.reverse(...args)` resolves with the arguments reversed, other methods fail.
+struct Echo;
+
+impl Binding for Echo {
+    fn name(&self) -> &str {
+        "ECHO"
+    }
+
+    fn call<'a>(
+        &'a self,
+        method: &'a str,
+        args: Vec<Value>,
+    ) -> LocalBoxFuture<'a, Result<Value, AnyError>> {
+        async move {
+            match method {
+                "reverse" => Ok(Value::Array(args.into_iter().rev().collect())),
+                _ => Err(type_error(format!("Unknown method {method}"))),
+            }
+        }
+        .boxed_local()
+    }
+}
+
+#[test]
+fn binding_round_trip() {
+    run(async {
+        let options = WorkerOptions {
+            bindings: vec![Arc::new(Echo)],
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith((async () => {
+                const reversed = await ECHO.reverse(1, "two", { three: [3] }, null);
+                const error = await ECHO.missing().catch((err) => err.message);
+
+                return Response.json({ reversed, error, thenable: typeof ECHO.then });
+              })());
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+        res.assert_ok();
+
+        let body: Value = deno_core::serde_json::from_str(&res.text()).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "reversed": [null, { "three": [3] }, "two", 1],
+                "error": "Unknown method missing",
+                "thenable": "undefined",
+            })
+        );
+    });
+}
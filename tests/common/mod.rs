@@ -0,0 +1,214 @@
+// Each test binary only uses some of the helpers
+#![allow(dead_code)]
+
+use std::future::Future;
+
+use actix_web::web;
+use actix_web::App;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::HttpServer;
+use bytes::Bytes;
+use bytes::BytesMut;
+use openworkers_runtime::AnyError;
+use openworkers_runtime::FetchInit;
+use openworkers_runtime::FileStream;
+use openworkers_runtime::LogEvent;
+use openworkers_runtime::ResponseBody;
+use openworkers_runtime::Script;
+use openworkers_runtime::Task;
+use openworkers_runtime::Worker;
+use openworkers_runtime::WorkerOptions;
+use tokio::sync::oneshot;
+
+pub type HttpResponse02 = http_v02::Response<ResponseBody>;
+
+/// Run a future on a current thread runtime, workers are `!Send` and spawn local tasks.
+pub fn run<F: Future>(future: F) -> F::Output {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    tokio::task::LocalSet::new().block_on(&rt, future)
+}
+
+/// Inline worker script.
+pub fn script(code: &str) -> Script {
+    Script {
+        specifier: openworkers_runtime::worker_url("test"),
+        code: Some(code.to_string().into()),
+        env: None,
+    }
+}
+
+pub async fn worker(code: &str, options: WorkerOptions) -> Worker {
+    Worker::new(script(code), None, options).await.unwrap()
+}
+
+/// Worker whose console output is received on the returned channel.
+pub async fn worker_with_logs(
+    code: &str,
+    options: WorkerOptions,
+) -> (Worker, std::sync::mpsc::Receiver<LogEvent>) {
+    let (log_tx, log_rx) = std::sync::mpsc::channel();
+
+    let worker = Worker::new(script(code), Some(log_tx), options)
+        .await
+        .unwrap();
+
+    (worker, log_rx)
+}
+
+/// Messages logged so far, without their trailing newline.
+pub fn logs(log_rx: &std::sync::mpsc::Receiver<LogEvent>) -> Vec<String> {
+    log_rx
+        .try_iter()
+        .map(|event| event.message.trim_end().to_string())
+        .collect()
+}
+
+pub fn get(path: &str) -> http_v02::Request<Bytes> {
+    request("GET", path, Bytes::new())
+}
+
+/// Request for `path` on `http://localhost`, so workers can parse `request.url`.
+pub fn request(method: &str, path: &str, body: impl Into<Bytes>) -> http_v02::Request<Bytes> {
+    http_v02::Request::builder()
+        .method(method)
+        .uri(format!("http://localhost{path}"))
+        .body(body.into())
+        .unwrap()
+}
+
+/// Response of a fetch task, with its body read to the end.
+pub struct Fetched {
+    pub result: Result<(), AnyError>,
+    pub response: Option<http_v02::Response<Bytes>>,
+    /// Whether the body was streamed, rather than sent in one piece.
+    pub streamed: bool,
+    pub trailers: Vec<(String, String)>,
+}
+
+impl Fetched {
+    /// Panic with the error of the task if it failed.
+    pub fn assert_ok(&self) {
+        if let Err(err) = &self.result {
+            panic!("fetch task failed: {err}");
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.response.as_ref().unwrap().status().as_u16()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let headers = self.response.as_ref().unwrap().headers();
+        headers.get(name).map(|value| value.to_str().unwrap())
+    }
+
+    pub fn body(&self) -> &[u8] {
+        self.response.as_ref().unwrap().body()
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8(self.body().to_vec()).unwrap()
+    }
+}
+
+/// Execute a fetch task, reading the response while the worker produces it.
+pub async fn fetch(worker: &mut Worker, req: http_v02::Request<Bytes>) -> Fetched {
+    fetch_with(worker, req, |init| init).await
+}
+
+/// Execute a fetch task built with the given `FetchInit` options.
+pub async fn fetch_with(
+    worker: &mut Worker,
+    req: http_v02::Request<Bytes>,
+    init: impl FnOnce(FetchInit) -> FetchInit,
+) -> Fetched {
+    let (res_tx, res_rx) = oneshot::channel::<HttpResponse02>();
+
+    let task = Task::Fetch(Some(init(FetchInit::new(req, res_tx))));
+
+    let (result, received) = tokio::join!(worker.exec(task), receive(res_rx));
+
+    let (response, streamed, trailers) = match received {
+        Some((response, streamed, trailers)) => (Some(response), streamed, trailers),
+        None => (None, false, vec![]),
+    };
+
+    Fetched {
+        result,
+        response,
+        streamed,
+        trailers,
+    }
+}
+
+async fn receive(
+    res_rx: oneshot::Receiver<HttpResponse02>,
+) -> Option<(http_v02::Response<Bytes>, bool, Vec<(String, String)>)> {
+    let (parts, body) = res_rx.await.ok()?.into_parts();
+
+    let streamed = matches!(body, ResponseBody::Stream(_));
+    let (body, trailers) = read_body(body).await;
+
+    Some((
+        http_v02::Response::from_parts(parts, body),
+        streamed,
+        trailers,
+    ))
+}
+
+/// Read a response body to the end, with the trailers of a streamed body.
+pub async fn read_body(body: ResponseBody) -> (Bytes, Vec<(String, String)>) {
+    match body {
+        ResponseBody::None | ResponseBody::Empty => (Bytes::new(), vec![]),
+        ResponseBody::Bytes(bytes) => (bytes, vec![]),
+        ResponseBody::Stream(mut stream) => {
+            let mut buf = BytesMut::new();
+
+            while let Some(chunk) = stream.recv().await {
+                buf.extend_from_slice(&chunk);
+            }
+
+            (buf.freeze(), stream.trailers().await)
+        }
+        ResponseBody::File(path) => {
+            let mut file = FileStream::open(path).await.unwrap();
+            let mut buf = BytesMut::new();
+
+            while let Some(chunk) = file.recv().await {
+                buf.extend_from_slice(&chunk.unwrap());
+            }
+
+            (buf.freeze(), vec![])
+        }
+    }
+}
+
+/// Local upstream server for outbound `fetch`, running on its own thread
+/// until the test process exits. Returns its base url, e.g. `http://127.0.0.1:1234`.
+pub fn upstream<F, Fut>(handler: F) -> String
+where
+    F: Fn(HttpRequest, web::Bytes) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = HttpResponse> + 'static,
+{
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        actix_web::rt::System::new().block_on(async move {
+            HttpServer::new(move || App::new().default_service(web::to(handler.clone())))
+                .workers(1)
+                .disable_signals()
+                .listen(listener)
+                .unwrap()
+                .run()
+                .await
+        })
+    });
+
+    format!("http://{addr}")
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use openworkers_runtime::Script;
+use openworkers_runtime::Worker;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::script;
+
+const ENV_WORKER: &str = r#"
+addEventListener("fetch", (event) => {
+  event.respondWith(Response.json({ env, pwned: globalThis.pwned ?? null }));
+});
+"#;
+
+async fn env_worker(env: &str) -> Result<Worker, String> {
+    let script = Script {
+        env: Some(env.to_string()),
+        ..script(ENV_WORKER)
+    };
+
+    Worker::new(script, None, Default::default())
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[test]
+fn env_value_with_quotes_is_escaped() {
+    run(async {
+        let env = r#"{"NAME":"\"}, globalThis.pwned = true, {\"","LINE":"a b"}"#;
+
+        let mut worker = env_worker(env).await.unwrap();
+
+        let res = fetch(&mut worker, get("/")).await;
+        res.assert_ok();
+
+        let body: deno_core::serde_json::Value =
+            deno_core::serde_json::from_str(&res.text()).unwrap();
+
+        assert_eq!(body["env"]["NAME"], r#""}, globalThis.pwned = true, {""#);
+        assert_eq!(body["env"]["LINE"], "a\u{2028}b");
+        assert_eq!(body["pwned"], deno_core::serde_json::Value::Null);
+    });
+}
+
+#[test]
+fn env_must_be_an_object() {
+    run(async {
+        for env in ["[1, 2]", "\"text\"", "null", "42"] {
+            let err = env_worker(env).await.err().expect(env);
+            assert_eq!(err, "Worker env must be a JSON object", "{env}");
+        }
+    });
+}
+
+#[test]
+fn env_must_be_valid_json() {
+    run(async {
+        for env in [
+            "{NAME: 1}",
+            "{\"A\": NaN}",
+            "{} ; globalThis.pwned = true",
+            "",
+        ] {
+            let err = env_worker(env).await.err().expect(env);
+            assert!(
+                err.starts_with("Worker env is not valid JSON"),
+                "{env}: {err}"
+            );
+        }
+    });
+}
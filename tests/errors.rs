@@ -0,0 +1,303 @@
+mod common;
+
+use bytes::Bytes;
+use openworkers_runtime::AnyError;
+use openworkers_runtime::RuntimeLimits;
+use openworkers_runtime::ScheduledInit;
+use openworkers_runtime::ScheduledOverlapPolicy;
+use openworkers_runtime::Task;
+use openworkers_runtime::TerminatedError;
+use openworkers_runtime::TerminationReason;
+use openworkers_runtime::Worker;
+use openworkers_runtime::WorkerOptions;
+use tokio::sync::oneshot;
+
+use common::fetch_with;
+use common::get;
+use common::request;
+use common::run;
+use common::script;
+use common::worker;
+
+#[test]
+fn init_errors_are_returned() {
+    run(async {
+        let err = Worker::new(
+            script(r#"throw new Error("broken worker");"#),
+            None,
+            Default::default(),
+        )
+        .await
+        .err()
+        .expect("worker should fail to start");
+
+        assert!(err.to_string().contains("broken worker"), "{err}");
+    });
+}
+
+#[test]
+fn scheduled_double_respond_fails_gracefully() {
+    run(async {
+        // A miscounted task would refuse the next one
+        let options = WorkerOptions {
+            scheduled_overlap: ScheduledOverlapPolicy::Reject,
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            addEventListener("scheduled", (event) => {
+              event.waitUntil(Promise.resolve());
+              event.waitUntil(Promise.resolve()).catch((err) => {
+                event.retry({ delaySeconds: 1 });
+                console.log(err.message);
+              });
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        for time in 0..2 {
+            let (res_tx, res_rx) = oneshot::channel();
+            let task = Task::Scheduled(Some(ScheduledInit::new(res_tx, time)));
+
+            worker.exec(task).await.unwrap();
+
+            // The first respond won, the second was refused
+            assert!(!res_rx.await.unwrap().retry);
+        }
+    });
+}
+
+#[test]
+fn scheduled_uncaught_double_respond_fails_the_task() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("scheduled", (event) => {
+              event.waitUntil(Promise.resolve());
+              event.waitUntil(Promise.resolve());
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let (res_tx, res_rx) = oneshot::channel();
+        let task = Task::Scheduled(Some(ScheduledInit::new(res_tx, 0)));
+
+        assert!(worker.exec(task).await.is_err());
+        res_rx.await.unwrap();
+
+        // The worker is still usable
+        let (res_tx, res_rx) = oneshot::channel();
+        let task = Task::Scheduled(Some(ScheduledInit::new(res_tx, 1)));
+
+        assert!(worker.exec(task).await.is_err());
+        res_rx.await.unwrap();
+    });
+}
+
+/// Execute a fetch task, with the reason sent on its error channel if any.
+async fn fetch_reason(
+    worker: &mut Worker,
+    req: http_v02::Request<Bytes>,
+) -> (Result<(), AnyError>, Option<TerminationReason>) {
+    let (error_tx, error_rx) = oneshot::channel();
+
+    let res = fetch_with(worker, req, |init| init.with_error_tx(error_tx)).await;
+
+    (res.result, error_rx.await.ok())
+}
+
+#[test]
+fn success_sends_no_reason() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(new Response("ok"));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let (result, reason) = fetch_reason(&mut worker, get("/")).await;
+
+        result.unwrap();
+        assert!(reason.is_none());
+    });
+}
+
+#[test]
+fn exception_reason() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", () => {
+              throw new Error("handler failed");
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let (result, reason) = fetch_reason(&mut worker, get("/")).await;
+
+        assert!(result.is_err());
+        match reason {
+            Some(TerminationReason::Exception(message)) => {
+                assert!(message.contains("handler failed"), "{message}")
+            }
+            reason => panic!("unexpected reason {reason:?}"),
+        }
+    });
+}
+
+#[test]
+fn error_reason() {
+    run(async {
+        // Scheduled only, fetch tasks are refused
+        let mut worker = worker(
+            r#"
+            addEventListener("scheduled", (event) => {
+              event.waitUntil(Promise.resolve());
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let (result, reason) = fetch_reason(&mut worker, get("/")).await;
+
+        assert!(result.is_err());
+        match reason {
+            Some(TerminationReason::Error(message)) => {
+                assert_eq!(message, "no fetch event listener registered")
+            }
+            reason => panic!("unexpected reason {reason:?}"),
+        }
+    });
+}
+
+#[test]
+fn pass_through_reason() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.passThroughOnException();
+              throw new Error("handler failed");
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let (result, reason) = fetch_reason(&mut worker, get("/")).await;
+
+        assert!(result.is_err());
+        assert!(
+            matches!(reason, Some(TerminationReason::PassThrough(_))),
+            "{reason:?}"
+        );
+    });
+}
+
+#[test]
+fn headers_timeout_reason() {
+    run(async {
+        let options = WorkerOptions {
+            limits: RuntimeLimits {
+                headers_timeout_ms: Some(50),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(scheduler.wait(500).then(() => new Response()));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let (result, reason) = fetch_reason(&mut worker, get("/")).await;
+
+        assert!(result.is_err());
+        assert!(
+            matches!(reason, Some(TerminationReason::HeadersTimeout)),
+            "{reason:?}"
+        );
+    });
+}
+
+#[test]
+fn payload_too_large_reason() {
+    run(async {
+        let options = WorkerOptions {
+            limits: RuntimeLimits {
+                max_request_bytes: Some(4),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(new Response("ok"));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let (result, reason) = fetch_reason(&mut worker, request("POST", "/", "too large")).await;
+
+        assert!(result.is_err());
+        assert!(
+            matches!(reason, Some(TerminationReason::PayloadTooLarge)),
+            "{reason:?}"
+        );
+    });
+}
+
+#[test]
+fn memory_limit_reason() {
+    run(async {
+        let options = WorkerOptions {
+            limits: RuntimeLimits {
+                heap_max_mb: Some(16),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", () => {
+              const chunks = [];
+              while (true) chunks.push(new Array(100_000).fill(chunks.length));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let (result, reason) = fetch_reason(&mut worker, get("/")).await;
+
+        let err = result.unwrap_err();
+        assert!(err.downcast_ref::<TerminatedError>().is_some(), "{err}");
+        assert!(
+            matches!(reason, Some(TerminationReason::MemoryLimit)),
+            "{reason:?}"
+        );
+        assert!(worker.memory_pressure());
+    });
+}
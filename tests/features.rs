@@ -0,0 +1,134 @@
+mod common;
+
+use std::sync::Arc;
+
+use openworkers_runtime::BlobStore;
+use openworkers_runtime::Features;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::worker;
+
+#[test]
+fn disabled_fetch_is_not_exposed() {
+    run(async {
+        let options = WorkerOptions {
+            features: Features {
+                fetch: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              const types = [typeof fetch, typeof EventSource, typeof Request, typeof crypto];
+              event.respondWith(new Response(types.join(",")));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "undefined,undefined,function,object");
+    });
+}
+
+const UUID_WORKER: &str = r#"
+addEventListener("fetch", (event) => {
+  event.respondWith(new Response(`${crypto.randomUUID()} ${crypto.randomUUID()}`));
+});
+"#;
+
+async fn uuids(random_seed: Option<u64>) -> String {
+    let options = WorkerOptions {
+        random_seed,
+        ..Default::default()
+    };
+
+    let mut worker = worker(UUID_WORKER, options).await;
+
+    let res = fetch(&mut worker, get("/")).await;
+    res.assert_ok();
+
+    res.text()
+}
+
+#[test]
+fn seeded_random_uuid_is_deterministic() {
+    run(async {
+        let seeded = uuids(Some(42)).await;
+
+        assert_eq!(seeded, uuids(Some(42)).await);
+        assert_ne!(seeded, uuids(Some(43)).await);
+        assert_ne!(uuids(None).await, uuids(None).await);
+
+        // Still distinct, well formed v4 UUIDs
+        let (first, second) = seeded.split_once(' ').unwrap();
+        assert_ne!(first, second);
+
+        for uuid in [first, second] {
+            assert_eq!(uuid.len(), 36, "{uuid}");
+            assert_eq!(&uuid[14..15], "4", "{uuid}");
+        }
+    });
+}
+
+#[test]
+fn shared_blob_store_resolves_object_urls_across_workers() {
+    run(async {
+        let store = Arc::new(BlobStore::default());
+
+        let options = WorkerOptions {
+            blob_store: Some(store.clone()),
+            ..Default::default()
+        };
+
+        let mut writer = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              const url = URL.createObjectURL(new Blob(["shared blob"]));
+              event.respondWith(new Response(url));
+            });
+            "#,
+            options.clone(),
+        )
+        .await;
+
+        let res = fetch(&mut writer, get("/")).await;
+        res.assert_ok();
+
+        let url = res.text();
+        assert!(url.starts_with("blob:"), "{url}");
+
+        const READER: &str = r#"
+            addEventListener("fetch", (event) => {
+              const url = new URL(event.request.url).searchParams.get("url");
+
+              event.respondWith(
+                fetch(url)
+                  .then((res) => res.text())
+                  .catch((err) => `${err.name}`)
+                  .then((text) => new Response(text))
+              );
+            });
+            "#;
+
+        let path = format!("/?url={url}");
+
+        let mut reader = worker(READER, options).await;
+        let res = fetch(&mut reader, get(&path)).await;
+        assert_eq!(res.text(), "shared blob");
+
+        // Another store doesn't know the url
+        let mut other = worker(READER, Default::default()).await;
+        let res = fetch(&mut other, get(&path)).await;
+        assert_eq!(res.text(), "TypeError");
+    });
+}
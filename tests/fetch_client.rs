@@ -0,0 +1,107 @@
+mod common;
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use actix_web::web;
+use actix_web::App;
+use actix_web::HttpResponse;
+use actix_web::HttpServer;
+use openworkers_runtime::FetchClientConfig;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::worker;
+
+/// Local upstream counting the connections it accepted.
+fn counting_upstream() -> (String, Arc<AtomicUsize>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+
+    std::thread::spawn(move || {
+        actix_web::rt::System::new().block_on(async move {
+            HttpServer::new(|| {
+                App::new().default_service(web::to(|| async { HttpResponse::Ok().body("ok") }))
+            })
+            .on_connect(move |_, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .workers(1)
+            .disable_signals()
+            .listen(listener)
+            .unwrap()
+            .run()
+            .await
+        })
+    });
+
+    (format!("http://{addr}"), connections)
+}
+
+/// Number of connections opened for 5 sequential fetches with the client config.
+async fn connections_for(config: FetchClientConfig) -> usize {
+    let (url, connections) = counting_upstream();
+
+    let options = WorkerOptions {
+        fetch_client: Some(config),
+        ..Default::default()
+    };
+
+    let mut worker = worker(
+        &format!(
+            r#"
+            addEventListener("fetch", (event) => {{
+              event.respondWith((async () => {{
+                const bodies = [];
+
+                for (let i = 0; i < 5; i++) {{
+                  const res = await fetch("{url}/" + i);
+                  bodies.push(await res.text());
+                }}
+
+                return new Response(bodies.join(","));
+              }})());
+            }});
+            "#
+        ),
+        options,
+    )
+    .await;
+
+    let res = fetch(&mut worker, get("/")).await;
+
+    res.assert_ok();
+    assert_eq!(res.text(), "ok,ok,ok,ok,ok");
+
+    connections.load(Ordering::SeqCst)
+}
+
+#[test]
+fn idle_connections_are_reused() {
+    run(async {
+        let config = FetchClientConfig {
+            pool_max_idle_per_host: Some(1),
+            ..Default::default()
+        };
+
+        assert_eq!(connections_for(config).await, 1);
+    });
+}
+
+#[test]
+fn connections_are_not_reused_without_idle_pool() {
+    run(async {
+        let config = FetchClientConfig {
+            pool_max_idle_per_host: Some(0),
+            ..Default::default()
+        };
+
+        assert_eq!(connections_for(config).await, 5);
+    });
+}
@@ -0,0 +1,230 @@
+mod common;
+
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+
+use actix_web::HttpResponse;
+use deno_core::serde_json;
+use deno_core::serde_json::json;
+use deno_core::serde_json::Value;
+use tokio::sync::oneshot;
+
+use common::fetch;
+use common::fetch_with;
+use common::get;
+use common::request;
+use common::run;
+use common::upstream;
+use common::worker;
+
+#[test]
+fn trailers_are_sent_after_the_body() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              let digest;
+
+              const body = new ReadableStream({
+                start(controller) {
+                  controller.enqueue(new TextEncoder().encode("hello"));
+                  digest = "5";
+                  controller.close();
+                },
+              });
+
+              event.respondWith(new Response(body), {
+                trailers: Promise.resolve().then(() => ({ "x-length": digest })),
+              });
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert!(res.streamed);
+        assert_eq!(res.text(), "hello");
+        assert_eq!(res.trailers, [("x-length".to_string(), "5".to_string())]);
+    });
+}
+
+#[test]
+fn early_hints_are_sent_before_the_response() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.earlyHints({ link: "</style.css>; rel=preload; as=style" });
+              event.respondWith(scheduler.wait(10).then(() => new Response("page")));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let (hints_tx, mut hints_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let res = fetch_with(&mut worker, get("/"), |init| {
+            init.with_early_hints(hints_tx)
+        })
+        .await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "page");
+
+        let hints = hints_rx.recv().await.unwrap();
+
+        assert_eq!(hints.status(), 103);
+        assert_eq!(
+            hints.headers()["link"],
+            "</style.css>; rel=preload; as=style"
+        );
+        assert!(hints_rx.try_recv().is_err());
+    });
+}
+
+#[test]
+fn request_exposes_raw_path_and_query() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              const { path, query } = event.request;
+              event.respondWith(Response.json({ path, query }));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/search%20all?q=a%26b&page=2")).await;
+
+        res.assert_ok();
+        assert_eq!(
+            res.text(),
+            r#"{"path":"/search%20all","query":"q=a%26b&page=2"}"#
+        );
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        assert_eq!(res.text(), r#"{"path":"/","query":""}"#);
+    });
+}
+
+#[test]
+fn request_cf_holds_the_host_metadata() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(Response.json(event.request.cf ?? null));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let metadata = HashMap::from([
+            ("country".to_string(), json!("FR")),
+            ("asn".to_string(), json!(12322)),
+        ]);
+
+        let res = fetch_with(&mut worker, get("/"), |init| init.with_metadata(metadata)).await;
+
+        res.assert_ok();
+
+        let cf: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(cf, json!({ "country": "FR", "asn": 12322 }));
+
+        // Without metadata
+        let res = fetch(&mut worker, get("/")).await;
+        assert_eq!(res.text(), "null");
+    });
+}
+
+#[test]
+fn host_abort_cancels_the_worker_subrequest() {
+    let url = upstream(|_, _| async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        HttpResponse::Ok().finish()
+    });
+
+    run(async {
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith(
+                    fetch("{url}", {{ signal: event.request.signal }}).catch(
+                      (err) => new Response(err.name, {{ status: 499 }})
+                    )
+                  );
+                }});
+                "#
+            ),
+            Default::default(),
+        )
+        .await;
+
+        let (abort_tx, abort_rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let _ = abort_tx.send(());
+        });
+
+        let start = Instant::now();
+        let res = fetch_with(&mut worker, get("/"), |init| init.with_abort(abort_rx)).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 499);
+        assert_eq!(res.text(), "AbortError");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    });
+}
+
+const METHOD_WORKER: &str = r#"
+addEventListener("fetch", (event) => {
+  event.respondWith(new Response(event.request.method));
+});
+"#;
+
+#[test]
+fn request_method_is_normalized() {
+    run(async {
+        let mut worker = worker(METHOD_WORKER, Default::default()).await;
+
+        for (method, expected) in [("get", "GET"), ("Post", "POST"), ("PATCH", "PATCH")] {
+            let res = fetch(&mut worker, request(method, "/", "")).await;
+
+            res.assert_ok();
+            assert_eq!(res.text(), expected);
+        }
+
+        // Extension methods keep their case
+        let res = fetch(&mut worker, request("purge", "/", "")).await;
+        assert_eq!(res.text(), "purge");
+    });
+}
+
+#[test]
+fn unsupported_request_method_is_rejected() {
+    run(async {
+        let mut worker = worker(METHOD_WORKER, Default::default()).await;
+
+        for method in ["TRACE", "connect"] {
+            let res = fetch(&mut worker, request(method, "/", "")).await;
+
+            let err = res.result.unwrap_err();
+            let expected = format!("Unsupported request method {}", method.to_uppercase());
+            assert!(err.to_string().contains(&expected), "{err}");
+        }
+
+        // The worker still serves valid methods
+        fetch(&mut worker, get("/")).await.assert_ok();
+    });
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use openworkers_runtime::DefaultResponse;
+use openworkers_runtime::StaticRoute;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::worker;
+
+fn default_response() -> WorkerOptions {
+    WorkerOptions {
+        default_response: DefaultResponse::new(503, "worker unavailable")
+            .with_header("content-type", "text/plain"),
+        ..Default::default()
+    }
+}
+
+fn assert_default_response(res: &common::Fetched) {
+    assert_eq!(res.status(), 503);
+    assert_eq!(res.header("content-type"), Some("text/plain"));
+    assert_eq!(res.text(), "worker unavailable");
+}
+
+#[test]
+fn default_response_when_the_worker_never_responds() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", () => {});
+            "#,
+            default_response(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_default_response(&res);
+    });
+}
+
+#[test]
+fn default_response_when_the_listener_throws() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", () => {
+              throw new Error("handler failed");
+            });
+            "#,
+            default_response(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        assert!(res.result.is_err());
+        assert_default_response(&res);
+
+        // The response sender was released, the worker still serves
+        let res = fetch(&mut worker, get("/")).await;
+        assert_default_response(&res);
+    });
+}
+
+#[test]
+fn default_response_when_the_response_promise_rejects() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(Promise.reject(new Error("upstream failed")));
+            });
+            "#,
+            default_response(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        assert_default_response(&res);
+    });
+}
+
+#[test]
+fn static_route_is_answered_without_the_worker() {
+    run(async {
+        let options = WorkerOptions {
+            static_routes: vec![
+                StaticRoute::new("/health", 200, "ok").with_header("x-static", "1"),
+                StaticRoute::new("/assets/*", 404, "no assets"),
+            ],
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            let calls = 0;
+
+            addEventListener("fetch", (event) => {
+              calls++;
+              event.respondWith(new Response(`worker ${calls}`));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/health")).await;
+        res.assert_ok();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.header("x-static"), Some("1"));
+        assert_eq!(res.text(), "ok");
+
+        let res = fetch(&mut worker, get("/assets/app.js")).await;
+        assert_eq!(res.status(), 404);
+        assert_eq!(res.text(), "no assets");
+
+        // Neither request reached the listener
+        let res = fetch(&mut worker, get("/healthz")).await;
+        assert_eq!(res.text(), "worker 1");
+    });
+}
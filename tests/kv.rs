@@ -0,0 +1,143 @@
+mod common;
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use deno_core::futures::future::LocalBoxFuture;
+use deno_core::futures::FutureExt;
+use openworkers_runtime::AnyError;
+use openworkers_runtime::HashMapKvStore;
+use openworkers_runtime::KvStore;
+use openworkers_runtime::RuntimeLimits;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::worker;
+
+/// Store whose calls never complete, like an unreachable backend.
+struct StalledKvStore;
+
+impl KvStore for StalledKvStore {
+    fn get<'a>(&'a self, _key: &'a str) -> LocalBoxFuture<'a, Result<Option<Bytes>, AnyError>> {
+        std::future::pending().boxed_local()
+    }
+
+    fn put<'a>(&'a self, _key: &'a str, _value: Bytes) -> LocalBoxFuture<'a, Result<(), AnyError>> {
+        std::future::pending().boxed_local()
+    }
+
+    fn delete<'a>(&'a self, _key: &'a str) -> LocalBoxFuture<'a, Result<(), AnyError>> {
+        std::future::pending().boxed_local()
+    }
+
+    fn list<'a>(
+        &'a self,
+        _prefix: Option<&'a str>,
+    ) -> LocalBoxFuture<'a, Result<Vec<String>, AnyError>> {
+        std::future::pending().boxed_local()
+    }
+}
+
+#[test]
+fn kv_round_trip() {
+    run(async {
+        let kv = HashMapKvStore::new();
+
+        let options = WorkerOptions {
+            kv: Some(Arc::new(kv.clone())),
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith((async () => {
+                await env.KV.put("user:1", "alice");
+                await env.KV.put("user:2", JSON.stringify({ name: "bob" }));
+                await env.KV.put("other", "x");
+
+                const name = await env.KV.get("user:1");
+                const { name: other } = await env.KV.get("user:2", "json");
+                const keys = await env.KV.list({ prefix: "user:" });
+
+                await env.KV.delete("other");
+                const missing = await env.KV.get("other");
+
+                return Response.json({ name, other, keys, missing });
+              })());
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(
+            res.text(),
+            r#"{"name":"alice","other":"bob","keys":["user:1","user:2"],"missing":null}"#
+        );
+
+        // Values are stored by the host store
+        let stored = kv.get("user:1").await.unwrap();
+        assert_eq!(stored, Some(Bytes::from("alice")));
+    });
+}
+
+#[test]
+fn kv_timeout_rejects_in_worker() {
+    run(async {
+        let options = WorkerOptions {
+            kv: Some(Arc::new(StalledKvStore)),
+            limits: RuntimeLimits {
+                kv_timeout_ms: Some(50),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(
+                env.KV.get("key").then(
+                  () => new Response("resolved"),
+                  (err) => new Response(err.message, { status: 504 })
+                )
+              );
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 504);
+        assert!(res.text().contains("KV call timed out"), "{}", res.text());
+    });
+}
+
+#[test]
+fn kv_without_store_is_not_exposed() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(new Response(String(globalThis.env?.KV)));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "undefined");
+    });
+}
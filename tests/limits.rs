@@ -0,0 +1,431 @@
+mod common;
+
+use std::time::Duration;
+use std::time::Instant;
+
+use deno_core::serde_json;
+use deno_core::serde_json::json;
+use deno_core::serde_json::Value;
+use openworkers_runtime::HeadersTimeoutError;
+use openworkers_runtime::InitTimeoutError;
+use openworkers_runtime::PayloadTooLargeError;
+use openworkers_runtime::RuntimeLimits;
+use openworkers_runtime::TerminationReason;
+use openworkers_runtime::Worker;
+use openworkers_runtime::WorkerOptions;
+use tokio::sync::oneshot;
+
+use common::fetch;
+use common::fetch_with;
+use common::get;
+use common::request;
+use common::run;
+use common::script;
+use common::worker;
+
+fn limits(limits: RuntimeLimits) -> WorkerOptions {
+    WorkerOptions {
+        limits,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn headers_timeout_fails_a_late_response() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            headers_timeout_ms: Some(100),
+            ..Default::default()
+        });
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(
+                scheduler.wait(1000).then(() => new Response("too late"))
+              );
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let (error_tx, error_rx) = oneshot::channel();
+
+        let start = Instant::now();
+        let res = fetch_with(&mut worker, get("/"), |init| init.with_error_tx(error_tx)).await;
+
+        let err = res.result.unwrap_err();
+        assert!(err.downcast_ref::<HeadersTimeoutError>().is_some(), "{err}");
+        assert!(matches!(
+            error_rx.await.unwrap(),
+            TerminationReason::HeadersTimeout
+        ));
+        assert!(res.response.is_none());
+        assert!(start.elapsed() < Duration::from_millis(900));
+    });
+}
+
+#[test]
+fn headers_timeout_spares_a_started_stream() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            headers_timeout_ms: Some(100),
+            ..Default::default()
+        });
+
+        // Head sent right away, the body takes longer than the timeout
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              const body = new ReadableStream({
+                async start(controller) {
+                  controller.enqueue(new TextEncoder().encode("slow "));
+                  await scheduler.wait(300);
+                  controller.enqueue(new TextEncoder().encode("body"));
+                  controller.close();
+                },
+              });
+
+              event.respondWith(new Response(body));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert!(res.streamed);
+        assert_eq!(res.text(), "slow body");
+    });
+}
+
+#[test]
+fn timers_over_the_limit_throw() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            max_pending_timers: Some(3),
+            ..Default::default()
+        });
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith((async () => {
+                const results = [];
+
+                const ids = [
+                  setTimeout(() => {}, 10_000),
+                  setInterval(() => {}, 10_000),
+                  setTimeout("globalThis.fired = true", 0),
+                ];
+
+                try {
+                  setTimeout(() => {}, 0);
+                } catch (err) {
+                  results.push(`${err.name}: ${err.message}`);
+                }
+
+                // Fired timers, string callbacks included, free their slot
+                await scheduler.wait(20);
+                results.push(String(globalThis.fired));
+                ids.push(setTimeout(() => {}, 10_000));
+
+                // Cleared timers too
+                clearTimeout(ids[0]);
+                clearInterval(ids[1]);
+                ids.push(setTimeout(() => {}, 0), setTimeout(() => {}, 0));
+
+                try {
+                  setInterval(() => {}, 0);
+                } catch (err) {
+                  results.push(err.message);
+                }
+
+                ids.forEach((id) => clearTimeout(id));
+                return Response.json(results);
+              })());
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(
+            res.text(),
+            r#"["RangeError: Too many pending timers: 3 (max 3)","true","Too many pending timers: 3 (max 3)"]"#
+        );
+    });
+}
+
+#[test]
+fn oversized_request_body_is_refused() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            max_request_bytes: Some(8),
+            ..Default::default()
+        });
+
+        let mut worker = worker(
+            r#"
+            let handled = 0;
+
+            addEventListener("fetch", (event) => {
+              handled++;
+              event.respondWith(
+                event.request.text().then((body) => new Response(`${handled} ${body}`))
+              );
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let (error_tx, error_rx) = oneshot::channel();
+
+        let res = fetch_with(&mut worker, request("POST", "/", "123456789"), |init| {
+            init.with_error_tx(error_tx)
+        })
+        .await;
+
+        let err = res.result.unwrap_err();
+        let err = err.downcast_ref::<PayloadTooLargeError>().unwrap();
+
+        assert_eq!((err.len, err.max), (9, 8));
+        assert!(matches!(
+            error_rx.await.unwrap(),
+            TerminationReason::PayloadTooLarge
+        ));
+        assert!(res.response.is_none());
+
+        // Bodies up to the limit reach the worker, the refused one never did
+        let res = fetch(&mut worker, request("POST", "/", "12345678")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "1 12345678");
+    });
+}
+
+#[test]
+fn init_timeout_interrupts_a_top_level_loop() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            init_timeout_ms: Some(200),
+            ..Default::default()
+        });
+
+        let start = Instant::now();
+
+        let err = Worker::new(script("while (true) {}"), None, options)
+            .await
+            .err()
+            .expect("an endless top-level loop should time out");
+
+        assert!(err.downcast_ref::<InitTimeoutError>().is_some(), "{err}");
+        assert!(start.elapsed() < Duration::from_secs(5));
+    });
+}
+
+#[test]
+fn init_timeout_does_not_outlive_the_initialization() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            init_timeout_ms: Some(50),
+            ..Default::default()
+        });
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(new Response("alive"));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        // Past the init deadline, the worker isn't terminated
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "alive");
+    });
+}
+
+/// Retains about a megabyte of heap per request.
+const ALLOCATING_WORKER: &str = r#"
+const retained = [];
+
+addEventListener("fetch", (event) => {
+  retained.push(new Array(128 * 1024).fill(retained.length));
+  event.respondWith(new Response(String(retained.length)));
+});
+"#;
+
+/// Serve requests until the worker comes under memory pressure, returns the
+/// number of requests served.
+async fn serve_until_pressure(worker: &mut Worker) -> usize {
+    for served in 1..=200 {
+        let res = fetch(worker, get("/")).await;
+
+        // The request reaching the limit still completes on the grace heap
+        res.assert_ok();
+        assert_eq!(res.text(), served.to_string());
+
+        if worker.memory_pressure() {
+            return served;
+        }
+    }
+
+    panic!("worker never came under memory pressure");
+}
+
+#[test]
+fn allocating_worker_comes_under_memory_pressure() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            heap_max_mb: Some(16),
+            ..Default::default()
+        });
+
+        let mut worker = worker(ALLOCATING_WORKER, options).await;
+        assert!(!worker.memory_pressure());
+
+        let served = serve_until_pressure(&mut worker).await;
+
+        // Not before a good part of the heap is used
+        assert!(served > 4, "{served}");
+    });
+}
+
+#[test]
+fn heap_grace_lets_the_worker_keep_serving() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            heap_max_mb: Some(16),
+            heap_grace_mb: Some(64),
+            ..Default::default()
+        });
+
+        let mut worker = worker(ALLOCATING_WORKER, options).await;
+
+        let served = serve_until_pressure(&mut worker).await;
+
+        // Well within the grace heap
+        for served in served + 1..=served + 16 {
+            let res = fetch(&mut worker, get("/")).await;
+
+            res.assert_ok();
+            assert_eq!(res.text(), served.to_string());
+        }
+    });
+}
+
+/// Responds without body (so without a default `Content-Type`) and with as many
+/// 10 bytes headers as the `n` query parameter. The error of `respondWith` if
+/// any is served on `/error`.
+const HEADERS_WORKER: &str = r#"
+let error = "none";
+
+addEventListener("fetch", (event) => {
+  const url = new URL(event.request.url);
+
+  if (url.pathname === "/error") {
+    event.respondWith(new Response(error));
+    return;
+  }
+
+  const headers = {};
+
+  for (let i = 0; i < Number(url.searchParams.get("n")); i++) {
+    headers[`x-h${i}`] = "v".repeat(10);
+  }
+
+  event.respondWith(new Response(null, { headers })).catch((err) => {
+    error = err.message;
+  });
+});
+"#;
+
+#[test]
+fn response_header_count_is_capped() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            max_response_headers: Some(2),
+            ..Default::default()
+        });
+
+        let mut worker = worker(HEADERS_WORKER, options).await;
+
+        let res = fetch(&mut worker, get("/?n=2")).await;
+        res.assert_ok();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.header("x-h1"), Some("vvvvvvvvvv"));
+
+        let res = fetch(&mut worker, get("/?n=3")).await;
+        assert_eq!(res.status(), 500);
+        assert_eq!(res.header("x-h0"), None);
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(res.text(), "Too many headers in response: 3 (max 2)");
+    });
+}
+
+#[test]
+fn response_header_size_is_capped() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            max_header_bytes: Some(40),
+            ..Default::default()
+        });
+
+        let mut worker = worker(HEADERS_WORKER, options).await;
+
+        // Names and values count, 14 bytes per header
+        let res = fetch(&mut worker, get("/?n=2")).await;
+        res.assert_ok();
+        assert_eq!(res.status(), 200);
+
+        let res = fetch(&mut worker, get("/?n=3")).await;
+        assert_eq!(res.status(), 500);
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(res.text(), "Response headers too large: 42 bytes (max 40)");
+    });
+}
+
+#[test]
+fn worker_reads_its_limits() {
+    run(async {
+        let options = limits(RuntimeLimits {
+            heap_max_mb: Some(64),
+            max_pending_timers: Some(10),
+            ..Default::default()
+        });
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(Response.json(getLimits()));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+        res.assert_ok();
+
+        let limits: Value = serde_json::from_str(&res.text()).unwrap();
+        assert_eq!(limits["heapMaxMb"], json!(64));
+        assert_eq!(limits["maxPendingTimers"], json!(10));
+        assert_eq!(limits["maxLogEvents"], Value::Null);
+    });
+}
@@ -0,0 +1,362 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use deno_core::serde_json::json;
+use futures::FutureExt;
+use openworkers_runtime::ConsoleOptions;
+use openworkers_runtime::LogEvent;
+use openworkers_runtime::LogFlush;
+use openworkers_runtime::LogLevel;
+use openworkers_runtime::RuntimeLimits;
+use openworkers_runtime::Worker;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::logs;
+use common::run;
+use common::script;
+use common::worker_with_logs;
+
+#[test]
+fn logs_below_min_level_are_dropped_before_formatting() {
+    run(async {
+        let options = WorkerOptions {
+            min_log_level: LogLevel::Warn,
+            ..Default::default()
+        };
+
+        let (mut worker, log_rx) = worker_with_logs(
+            r#"
+            let formatted = 0;
+
+            // Serialized only if the call is formatted
+            const arg = { toJSON: () => formatted++ };
+
+            addEventListener("fetch", (event) => {
+              console.debug("debug", arg);
+              console.info("info", arg);
+              console.log(arg);
+              console.warn("warn");
+              console.error("error");
+
+              event.respondWith(new Response(String(formatted)));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "0");
+        assert_eq!(logs(&log_rx), ["warn", "error"]);
+    });
+}
+
+#[test]
+fn all_levels_are_logged_by_default() {
+    run(async {
+        let (mut worker, log_rx) = worker_with_logs(
+            r#"
+            addEventListener("fetch", (event) => {
+              console.debug("debug");
+              console.info("info");
+              console.log("log");
+              console.warn("warn");
+              console.error("error");
+
+              event.respondWith(new Response());
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        fetch(&mut worker, get("/")).await.assert_ok();
+
+        assert_eq!(logs(&log_rx), ["debug", "info", "log", "warn", "error"]);
+    });
+}
+
+#[test]
+fn plain_object_is_logged_with_its_fields() {
+    run(async {
+        let (mut worker, log_rx) = worker_with_logs(
+            r#"
+            addEventListener("fetch", (event) => {
+              console.log({ requestId: "abc", latency: 12 });
+              console.log("not structured", { requestId: "abc" });
+              console.log(new Map([["a", 1]]));
+
+              event.respondWith(new Response());
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        fetch(&mut worker, get("/")).await.assert_ok();
+
+        let events: Vec<LogEvent> = log_rx.try_iter().collect();
+        assert_eq!(events.len(), 3);
+
+        let fields = events[0].fields.as_ref().expect("structured fields");
+        assert_eq!(fields["requestId"], json!("abc"));
+        assert_eq!(fields["latency"], json!(12));
+
+        // Only a plain object logged on its own has fields
+        assert!(events[1].fields.is_none());
+        assert!(events[2].fields.is_none());
+    });
+}
+
+#[test]
+fn console_args_are_serialized() {
+    run(async {
+        let (mut worker, log_rx) = worker_with_logs(
+            r#"
+            addEventListener("fetch", (event) => {
+              console.info("user", 42, { roles: ["admin"] }, null, 10n);
+
+              event.respondWith(new Response());
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        fetch(&mut worker, get("/")).await.assert_ok();
+
+        let events: Vec<LogEvent> = log_rx.try_iter().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, "info");
+
+        // Values without a JSON representation are inspected
+        assert_eq!(
+            events[0].args.as_deref().unwrap(),
+            [
+                json!("user"),
+                json!(42),
+                json!({ "roles": ["admin"] }),
+                json!(null),
+                json!("10n")
+            ]
+        );
+    });
+}
+
+#[test]
+fn log_events_are_capped_per_exec() {
+    run(async {
+        let options = WorkerOptions {
+            limits: RuntimeLimits {
+                max_log_events: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let (mut worker, log_rx) = worker_with_logs(
+            r#"
+            addEventListener("fetch", (event) => {
+              for (let i = 0; i < 10; i++) {
+                console.log(String(i));
+              }
+
+              event.respondWith(new Response());
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        // The budget is reset on every exec
+        for _ in 0..2 {
+            fetch(&mut worker, get("/")).await.assert_ok();
+
+            assert_eq!(
+                logs(&log_rx),
+                [
+                    "0",
+                    "1",
+                    "2",
+                    "log rate limit exceeded (3 events), dropping logs"
+                ]
+            );
+        }
+    });
+}
+
+#[test]
+fn log_flush_waits_for_the_sink() {
+    run(async {
+        let (log_tx, log_rx) = std::sync::mpsc::channel();
+        let log_rx = Arc::new(Mutex::new(log_rx));
+
+        // Messages drained by the sink when the worker flushed
+        let flushed = Arc::new(Mutex::new(Vec::new()));
+
+        let log_flush: LogFlush = {
+            let log_rx = log_rx.clone();
+            let flushed = flushed.clone();
+
+            Arc::new(move || {
+                let log_rx = log_rx.clone();
+                let flushed = flushed.clone();
+
+                async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    flushed
+                        .lock()
+                        .unwrap()
+                        .extend(logs(&log_rx.lock().unwrap()));
+                }
+                .boxed_local()
+            })
+        };
+
+        let options = WorkerOptions {
+            log_flush: Some(log_flush),
+            ..Default::default()
+        };
+
+        let mut worker = Worker::new(
+            script(
+                r#"
+                addEventListener("fetch", (event) => {
+                  event.respondWith((async () => {
+                    console.log("before flush");
+                    await logFlush();
+                    console.log("after flush");
+
+                    return new Response();
+                  })());
+                });
+                "#,
+            ),
+            Some(log_tx),
+            options,
+        )
+        .await
+        .unwrap();
+
+        fetch(&mut worker, get("/")).await.assert_ok();
+
+        assert_eq!(*flushed.lock().unwrap(), ["before flush"]);
+        assert_eq!(logs(&log_rx.lock().unwrap()), ["after flush"]);
+    });
+}
+
+#[test]
+fn console_assert_and_count() {
+    run(async {
+        let (mut worker, log_rx) = worker_with_logs(
+            r#"
+            addEventListener("fetch", (event) => {
+              console.assert(true, "not logged");
+              console.assert(false, "expected %d", 1);
+              console.assert(false);
+
+              console.count();
+              console.count("hits");
+              console.count();
+              console.countReset();
+              console.count();
+
+              event.respondWith(new Response());
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        fetch(&mut worker, get("/")).await.assert_ok();
+
+        let events: Vec<LogEvent> = log_rx.try_iter().collect();
+
+        let messages: Vec<_> = events.iter().map(|e| e.message.trim_end()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Assertion failed: expected 1",
+                "Assertion failed",
+                "default: 1",
+                "hits: 1",
+                "default: 2",
+                "default: 1"
+            ]
+        );
+
+        // Failed assertions are errors by default
+        assert_eq!(events[0].level, "error");
+        assert_eq!(events[1].level, "error");
+    });
+}
+
+#[test]
+fn quiet_console_sends_no_events() {
+    run(async {
+        let options = WorkerOptions {
+            console: ConsoleOptions {
+                quiet: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let (mut worker, log_rx) = worker_with_logs(
+            r#"
+            addEventListener("fetch", (event) => {
+              console.log("log");
+              console.error("error");
+              console.assert(false);
+              console.count();
+              console.trace("trace");
+
+              event.respondWith(new Response("served"));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "served");
+        assert!(logs(&log_rx).is_empty());
+    });
+}
+
+#[test]
+fn dropped_log_receiver_does_not_fail_the_worker() {
+    run(async {
+        let (mut worker, log_rx) = worker_with_logs(
+            r#"
+            addEventListener("fetch", (event) => {
+              for (let i = 0; i < 100; i++) {
+                console.log("log", i);
+              }
+
+              event.respondWith(new Response("served"));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        drop(log_rx);
+
+        for _ in 0..2 {
+            let res = fetch(&mut worker, get("/")).await;
+
+            res.assert_ok();
+            assert_eq!(res.text(), "served");
+        }
+    });
+}
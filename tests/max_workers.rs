@@ -0,0 +1,48 @@
+mod common;
+
+use std::time::Duration;
+
+use openworkers_runtime::set_max_workers;
+use openworkers_runtime::Worker;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::script;
+use common::worker;
+
+const HELLO_WORKER: &str = r#"
+  addEventListener("fetch", (event) => {
+    event.respondWith(new Response("hello"));
+  });
+"#;
+
+// The worker limit is process wide, this binary holds a single test
+#[test]
+fn worker_waits_for_a_slot() {
+    set_max_workers(2);
+
+    run(async {
+        let first = worker(HELLO_WORKER, Default::default()).await;
+        let mut second = worker(HELLO_WORKER, Default::default()).await;
+
+        let third = Worker::new(script(HELLO_WORKER), None, Default::default());
+        tokio::pin!(third);
+
+        // Both slots are taken
+        let waiting = tokio::time::timeout(Duration::from_millis(300), &mut third).await;
+        assert!(waiting.is_err(), "third worker should wait for a slot");
+
+        // Live workers still serve meanwhile
+        fetch(&mut second, get("/")).await.assert_ok();
+
+        drop(first);
+
+        let mut third = tokio::time::timeout(Duration::from_secs(5), third)
+            .await
+            .expect("third worker should start once a slot is released")
+            .unwrap();
+
+        fetch(&mut third, get("/")).await.assert_ok();
+    });
+}
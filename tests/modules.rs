@@ -0,0 +1,94 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use openworkers_runtime::Script;
+use openworkers_runtime::SourceTransform;
+use openworkers_runtime::Worker;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::script;
+
+#[test]
+fn source_transform_injects_a_prelude() {
+    run(async {
+        let transformed = Arc::new(Mutex::new(Vec::new()));
+
+        let transform: SourceTransform = {
+            let transformed = transformed.clone();
+
+            Arc::new(move |specifier, code| {
+                transformed.lock().unwrap().push(specifier.to_string());
+
+                // On the first line, line numbers are kept
+                Ok(format!("globalThis.PRELUDE = \"injected\"; {code}"))
+            })
+        };
+
+        let options = WorkerOptions {
+            source_transform: Some(transform),
+            ..Default::default()
+        };
+
+        let mut worker = Worker::new(
+            script(
+                r#"
+                addEventListener("fetch", (event) => {
+                  const line = new Error().stack.split("\n")[1].split(":").at(-2);
+                  event.respondWith(new Response(`${PRELUDE} ${line}`));
+                });
+                "#,
+            ),
+            None,
+            options,
+        )
+        .await
+        .unwrap();
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "injected 3");
+        assert_eq!(*transformed.lock().unwrap(), ["worker:///test/worker.js"]);
+    });
+}
+
+#[test]
+fn worker_url_is_shown_in_error_stacks() {
+    run(async {
+        let script = Script {
+            specifier: openworkers_runtime::worker_url("my-worker"),
+            ..script(
+                r#"
+                function fail() {
+                  throw new Error("failed");
+                }
+
+                addEventListener("fetch", (event) => {
+                  try {
+                    fail();
+                  } catch (err) {
+                    event.respondWith(new Response(err.stack));
+                  }
+                });
+                "#,
+            )
+        };
+
+        let mut worker = Worker::new(script, None, Default::default()).await.unwrap();
+
+        let res = fetch(&mut worker, get("/")).await;
+        res.assert_ok();
+
+        let stack = res.text();
+        assert!(stack.starts_with("Error: failed"), "{stack}");
+        assert!(
+            stack.contains("at fail (worker:///my-worker/worker.js:3:"),
+            "{stack}"
+        );
+    });
+}
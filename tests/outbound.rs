@@ -0,0 +1,645 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use bytes::Bytes;
+use deno_core::serde_json;
+use deno_core::serde_json::Value;
+use openworkers_runtime::AcceptEncodingPolicy;
+use openworkers_runtime::FetchInterceptor;
+use openworkers_runtime::OutboundHeaderPolicy;
+use openworkers_runtime::OutboundRequest;
+use openworkers_runtime::RuntimeLimits;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::upstream;
+use common::worker;
+
+/// Upstream answering with the value of the header named by the request path.
+///
+/// `/redirect/<name>` redirects to `/<name>`, `/events/<name>` sends the value
+/// as a server-sent event.
+fn echo_header_upstream() -> String {
+    upstream(|req: HttpRequest, _| async move {
+        let path = req.path();
+
+        if let Some(name) = path.strip_prefix("/redirect/") {
+            return HttpResponse::Found()
+                .insert_header(("location", format!("/{name}")))
+                .finish();
+        }
+
+        let (events, name) = match path.strip_prefix("/events/") {
+            Some(name) => (true, name),
+            None => (false, path.trim_start_matches('/')),
+        };
+
+        let value = match req.headers().get(name) {
+            Some(value) => value.to_str().unwrap().to_string(),
+            None => return HttpResponse::NotFound().finish(),
+        };
+
+        match events {
+            true => HttpResponse::Ok()
+                .content_type("text/event-stream")
+                .body(format!("data: {value}\n\n")),
+            false => HttpResponse::Ok().body(value),
+        }
+    })
+}
+
+#[test]
+fn outbound_headers_over_the_limit_are_rejected() {
+    let url = echo_header_upstream();
+
+    run(async {
+        let options = WorkerOptions {
+            limits: RuntimeLimits {
+                max_fetch_headers: Some(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                const headers = (n) =>
+                  Array.from({{ length: n }}, (_, i) => [`x-h${{i}}`, String(i)]);
+
+                addEventListener("fetch", (event) => {{
+                  event.respondWith((async () => {{
+                    const allowed = await fetch("{url}/x-h2", {{ headers: headers(3) }});
+
+                    try {{
+                      await fetch("{url}/x-h0", {{ headers: headers(4) }});
+                      return new Response("not rejected", {{ status: 500 }});
+                    }} catch (err) {{
+                      return new Response(`${{await allowed.text()}} ${{err.message}}`);
+                    }}
+                  }})());
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        // Counts the `Accept` and `Accept-Language` defaults of fetch
+        assert_eq!(res.text(), "2 Too many headers in fetch request: 6 (max 5)");
+    });
+}
+
+#[test]
+fn deadline_header_reflects_the_time_left() {
+    let url = echo_header_upstream();
+
+    run(async {
+        let options = WorkerOptions {
+            deadline_header: Some("x-deadline-ms".to_string()),
+            limits: RuntimeLimits {
+                headers_timeout_ms: Some(2000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith((async () => {{
+                    await scheduler.wait(500);
+                    const left = await fetch("{url}/x-deadline-ms").then((r) => r.text());
+
+                    // Set by the worker, sent as is
+                    const own = await fetch("{url}/x-deadline-ms", {{
+                      headers: {{ "x-deadline-ms": "42" }},
+                    }}).then((r) => r.text());
+
+                    return Response.json({{ left: Number(left), own }});
+                  }})());
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let left = body["left"].as_u64().unwrap();
+
+        assert!((1000..=1500).contains(&left), "{left}ms left");
+        assert_eq!(body["own"], "42");
+    });
+}
+
+#[test]
+fn deadline_header_is_capped_by_the_subrequest_timeout() {
+    let url = echo_header_upstream();
+
+    run(async {
+        let options = WorkerOptions {
+            deadline_header: Some("x-deadline-ms".to_string()),
+            limits: RuntimeLimits {
+                headers_timeout_ms: Some(10_000),
+                subrequest_timeout_ms: Some(300),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith(fetch("{url}/x-deadline-ms"));
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "300");
+    });
+}
+
+#[test]
+fn deadline_header_is_omitted_without_a_deadline() {
+    let url = echo_header_upstream();
+
+    run(async {
+        let options = WorkerOptions {
+            deadline_header: Some("x-deadline-ms".to_string()),
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith(fetch("{url}/x-deadline-ms"));
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 404);
+    });
+}
+
+#[test]
+fn subrequest_timeout_rejects_before_the_slow_upstream_answers() {
+    let url = upstream(|_, _| async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        HttpResponse::Ok().body("too late")
+    });
+
+    run(async {
+        let options = WorkerOptions {
+            limits: RuntimeLimits {
+                subrequest_timeout_ms: Some(100),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith(
+                    fetch("{url}").catch(
+                      (err) => new Response(err.message, {{ status: 504 }})
+                    )
+                  );
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        let start = Instant::now();
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 504);
+        assert!(
+            res.text().contains("timed out after 100ms"),
+            "{}",
+            res.text()
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+    });
+}
+
+#[test]
+fn intercepted_fetch_never_reaches_the_network() {
+    run(async {
+        let interceptor: FetchInterceptor = Arc::new(|req: OutboundRequest| {
+            let body = format!(
+                "{} {} {}",
+                req.method(),
+                req.uri(),
+                String::from_utf8_lossy(req.body())
+            );
+
+            http_v02::Response::builder()
+                .status(201)
+                .header("x-mock", "1")
+                .body(Bytes::from(body))
+                .unwrap()
+        });
+
+        let options = WorkerOptions {
+            fetch_interceptor: Some(interceptor),
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(
+                fetch("https://api.invalid/items", { method: "POST", body: "new item" })
+              );
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 201);
+        assert_eq!(res.header("x-mock"), Some("1"));
+        assert_eq!(res.text(), "POST https://api.invalid/items new item");
+    });
+}
+
+#[test]
+fn outbound_header_policy_rewrites_headers() {
+    let url = echo_header_upstream();
+
+    run(async {
+        let policy: OutboundHeaderPolicy = Arc::new(|_url, headers| {
+            headers.remove("authorization");
+            headers.insert("x-worker-id", "test".parse().unwrap());
+        });
+
+        let options = WorkerOptions {
+            outbound_headers: Some(policy),
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith((async () => {{
+                    const headers = {{ authorization: "Bearer secret" }};
+
+                    const id = await fetch("{url}/x-worker-id", {{ headers }});
+                    const auth = await fetch("{url}/authorization", {{ headers }});
+
+                    return Response.json([await id.text(), auth.status]);
+                  }})());
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), r#"["test",404]"#);
+    });
+}
+
+/// Worker fetching `/redirect/<name>` and `/events/<name>` for the `x-worker-id`
+/// and `authorization` headers, responding with what the upstream received.
+const POLICY_HOPS_WORKER: &str = r#"
+  const headers = { authorization: "Bearer secret" };
+
+  const event = (url) =>
+    new Promise((resolve) => {
+      const source = new EventSource(url);
+
+      source.onmessage = (event) => {
+        source.close();
+        resolve(event.data);
+      };
+
+      source.onerror = () => {
+        source.close();
+        resolve(null);
+      };
+    });
+
+  addEventListener("fetch", (event) => {
+    event.respondWith((async () => {
+      const id = await fetch(`${UPSTREAM}/redirect/x-worker-id`, { headers });
+      const auth = await fetch(`${UPSTREAM}/redirect/authorization`, { headers });
+
+      return Response.json([
+        await id.text(),
+        auth.status,
+        await event(`${UPSTREAM}/events/x-worker-id`),
+      ]);
+    })());
+  });
+"#;
+
+#[test]
+fn outbound_header_policy_applies_to_every_hop() {
+    let url = echo_header_upstream();
+
+    run(async {
+        let policy: OutboundHeaderPolicy = Arc::new(|_url, headers| {
+            headers.remove("authorization");
+            headers.insert("x-worker-id", "test".parse().unwrap());
+        });
+
+        let options = WorkerOptions {
+            outbound_headers: Some(policy),
+            ..Default::default()
+        };
+
+        let code = format!("const UPSTREAM = {url:?};\n{POLICY_HOPS_WORKER}");
+        let mut worker = worker(&code, options).await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), r#"["test",404,"test"]"#);
+    });
+}
+
+#[test]
+fn deadline_header_is_set_on_redirects() {
+    let url = echo_header_upstream();
+
+    run(async {
+        let options = WorkerOptions {
+            deadline_header: Some("x-deadline-ms".to_string()),
+            limits: RuntimeLimits {
+                subrequest_timeout_ms: Some(300),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith(fetch("{url}/redirect/x-deadline-ms"));
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "300");
+    });
+}
+
+#[test]
+fn egress_audit_reports_outbound_hosts() {
+    let url = upstream(|_, _| async { HttpResponse::Ok().body("ok") });
+
+    // Bound then released, nothing listens on it
+    let closed = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    run(async {
+        let (egress_tx, egress_rx) = std::sync::mpsc::channel();
+
+        let options = WorkerOptions {
+            egress_audit: Some(egress_tx),
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith((async () => {{
+                    await fetch("{url}/a", {{ method: "POST" }});
+                    await fetch("http://{closed}/b").catch(() => {{}});
+                    return new Response();
+                  }})());
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        fetch(&mut worker, get("/")).await.assert_ok();
+
+        let records: Vec<_> = egress_rx
+            .try_iter()
+            .map(|record| (record.host, record.port, record.method, record.status))
+            .collect();
+
+        let port = url.rsplit(':').next().unwrap().parse().unwrap();
+
+        assert_eq!(
+            records,
+            [
+                (
+                    "127.0.0.1".to_string(),
+                    Some(port),
+                    "POST".to_string(),
+                    Some(200)
+                ),
+                (
+                    "127.0.0.1".to_string(),
+                    Some(closed.port()),
+                    "GET".to_string(),
+                    None
+                ),
+            ]
+        );
+    });
+}
+
+#[test]
+fn egress_audit_records_each_hop() {
+    let url = upstream(|req: HttpRequest, _| async move {
+        match req.path() {
+            "/start" => HttpResponse::Found()
+                .insert_header(("location", "/end"))
+                .finish(),
+            "/events" => HttpResponse::Ok()
+                .content_type("text/event-stream")
+                .body("data: hello\n\n"),
+            _ => HttpResponse::Ok().body("ok"),
+        }
+    });
+
+    run(async {
+        let (egress_tx, egress_rx) = std::sync::mpsc::channel();
+
+        let options = WorkerOptions {
+            egress_audit: Some(egress_tx),
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith((async () => {{
+                    await fetch("{url}/start");
+
+                    const data = await new Promise((resolve) => {{
+                      const source = new EventSource("{url}/events");
+                      source.onmessage = (event) => {{
+                        source.close();
+                        resolve(event.data);
+                      }};
+                    }});
+
+                    return new Response(data);
+                  }})());
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "hello");
+
+        let statuses: Vec<_> = egress_rx
+            .try_iter()
+            .map(|record| (record.method, record.status))
+            .collect();
+
+        let get = || "GET".to_string();
+
+        assert_eq!(
+            statuses,
+            [(get(), Some(302)), (get(), Some(200)), (get(), Some(200))]
+        );
+    });
+}
+
+/// Accept-Encoding received by the upstream, for a fetch without and with one.
+async fn accept_encoding(policy: AcceptEncodingPolicy) -> Vec<String> {
+    let url = echo_header_upstream();
+
+    let options = WorkerOptions {
+        fetch_accept_encoding: policy,
+        ..Default::default()
+    };
+
+    let mut worker = worker(
+        &format!(
+            r#"
+            addEventListener("fetch", (event) => {{
+              event.respondWith((async () => {{
+                const url = "{url}/accept-encoding";
+                const plain = await fetch(url).then((res) => res.text());
+                const own = await fetch(url, {{ headers: {{ "accept-encoding": "br" }} }})
+                  .then((res) => res.text());
+
+                return Response.json([plain, own]);
+              }})());
+            }});
+            "#
+        ),
+        options,
+    )
+    .await;
+
+    let res = fetch(&mut worker, get("/")).await;
+    res.assert_ok();
+
+    serde_json::from_str(&res.text()).unwrap()
+}
+
+#[test]
+fn accept_encoding_follows_the_policy() {
+    run(async {
+        let auto = accept_encoding(AcceptEncodingPolicy::Auto).await;
+        assert!(auto[0].contains("gzip"), "{auto:?}");
+        assert_eq!(auto[1], "br");
+
+        // Set by the worker, the header is kept
+        let identity = accept_encoding(AcceptEncodingPolicy::Identity).await;
+        assert_eq!(identity, ["identity", "br"]);
+    });
+}
+
+#[test]
+fn pending_fetches_are_counted() {
+    let url = upstream(|_, _| async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        HttpResponse::Ok().body("slow")
+    });
+
+    run(async {
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith((async () => {{
+                    const before = pendingFetches();
+                    const responses = [fetch("{url}/a"), fetch("{url}/b")];
+                    const during = pendingFetches();
+
+                    await Promise.all(responses);
+
+                    return Response.json([before, during, pendingFetches()]);
+                  }})());
+                }});
+                "#
+            ),
+            Default::default(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "[0,2,0]");
+    });
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::future::join_all;
+use openworkers_runtime::AnyError;
+use openworkers_runtime::FetchInit;
+use openworkers_runtime::HeadersTimeoutError;
+use openworkers_runtime::RuntimeLimits;
+use openworkers_runtime::Task;
+use openworkers_runtime::WorkerOptions;
+use openworkers_runtime::WorkerPool;
+use tokio::sync::oneshot;
+
+use common::get;
+use common::read_body;
+use common::run;
+use common::script;
+
+/// Responds with the path and the number of requests served by the worker so far.
+const COUNTING_WORKER: &str = r#"
+  let served = 0;
+
+  addEventListener("fetch", (event) => {
+    const { pathname } = new URL(event.request.url);
+    served++;
+
+    if (pathname === "/slow") {
+      event.respondWith(scheduler.wait(1000).then(() => new Response()));
+      return;
+    }
+
+    event.respondWith(new Response(`${pathname} ${served}`));
+  });
+"#;
+
+fn pool(size: usize, limits: RuntimeLimits) -> WorkerPool {
+    let options = move || WorkerOptions {
+        limits: limits.clone(),
+        ..Default::default()
+    };
+
+    WorkerPool::new(size, script(COUNTING_WORKER), None, Arc::new(options)).unwrap()
+}
+
+async fn pool_fetch(pool: &WorkerPool, uri: &str) -> (Result<(), AnyError>, Option<String>) {
+    let (res_tx, res_rx) = oneshot::channel();
+
+    let task = Task::Fetch(Some(FetchInit::new(get(uri), res_tx)));
+
+    let body = async {
+        let (body, _) = read_body(res_rx.await.ok()?.into_body()).await;
+        Some(body)
+    };
+
+    let (result, body) = tokio::join!(pool.exec(task), body);
+
+    let body = body.map(|body: Bytes| String::from_utf8(body.to_vec()).unwrap());
+
+    (result, body)
+}
+
+#[test]
+fn pool_dispatches_fetches_round_robin() {
+    run(async {
+        let pool = pool(4, RuntimeLimits::default());
+
+        for i in 0..100 {
+            let (result, body) = pool_fetch(&pool, &format!("/{i}")).await;
+
+            result.unwrap();
+            assert_eq!(body.unwrap(), format!("/{i} {}", i / 4 + 1));
+        }
+    });
+}
+
+#[test]
+fn pool_serves_concurrent_fetches() {
+    run(async {
+        let pool = pool(4, RuntimeLimits::default());
+        assert_eq!(pool.size(), 4);
+
+        let fetches = (0..100).map(|i| {
+            let pool = &pool;
+            async move { (i, pool_fetch(pool, &format!("/{i}")).await) }
+        });
+
+        let mut counts = vec![];
+
+        for (i, (result, body)) in join_all(fetches).await {
+            result.unwrap();
+
+            let body = body.unwrap();
+            let (path, count) = body.split_once(' ').unwrap();
+
+            assert_eq!(path, format!("/{i}"));
+            counts.push(count.parse::<u32>().unwrap());
+        }
+
+        // Each worker served a quarter of the requests
+        counts.sort();
+        let expected: Vec<u32> = (1..=25).flat_map(|count| [count; 4]).collect();
+        assert_eq!(counts, expected);
+    });
+}
+
+#[test]
+fn pool_recycles_a_worker_after_a_headers_timeout() {
+    run(async {
+        let pool = pool(
+            1,
+            RuntimeLimits {
+                headers_timeout_ms: Some(50),
+                ..Default::default()
+            },
+        );
+
+        let (result, body) = pool_fetch(&pool, "/").await;
+        result.unwrap();
+        assert_eq!(body.unwrap(), "/ 1");
+
+        let (result, _) = pool_fetch(&pool, "/").await;
+        result.unwrap();
+
+        let (result, body) = pool_fetch(&pool, "/slow").await;
+        let err = result.unwrap_err();
+        assert!(err.downcast_ref::<HeadersTimeoutError>().is_some(), "{err}");
+        assert!(body.is_none());
+
+        // Served by a fresh worker
+        let (result, body) = pool_fetch(&pool, "/").await;
+        result.unwrap();
+        assert_eq!(body.unwrap(), "/ 1");
+    });
+}
+
+/// Sends as many subrequests as the `n` query parameter.
+const SUBREQUEST_WORKER: &str = r#"
+  addEventListener("fetch", (event) => {
+    const n = Number(new URL(event.request.url).searchParams.get("n"));
+
+    const requests = Array.from({ length: n }, (_, i) => fetch(`https://example.com/${i}`));
+
+    event.respondWith(Promise.all(requests).then(() => new Response("done")));
+  });
+"#;
+
+#[test]
+fn pool_aggregates_usage_per_tenant() {
+    run(async {
+        let options = || WorkerOptions {
+            fetch_interceptor: Some(Arc::new(|_| http_v02::Response::new(Bytes::new()))),
+            ..Default::default()
+        };
+
+        let pool = WorkerPool::new(2, script(SUBREQUEST_WORKER), None, Arc::new(options)).unwrap();
+
+        let tasks = [("a", 1), ("b", 2), ("a", 1), ("b", 2), ("a", 1)];
+
+        for (tenant, n) in tasks {
+            let (res_tx, res_rx) = oneshot::channel();
+            let task = Task::Fetch(Some(FetchInit::new(get(&format!("/?n={n}")), res_tx)));
+
+            pool.exec_for(tenant, task).await.unwrap();
+            assert_eq!(res_rx.await.unwrap().status(), 200);
+        }
+
+        // Not accounted to any tenant
+        let (res_tx, _res_rx) = oneshot::channel();
+        let task = Task::Fetch(Some(FetchInit::new(get("/?n=5"), res_tx)));
+        pool.exec(task).await.unwrap();
+
+        let usage = pool.tenant_usage();
+        assert_eq!(usage.len(), 2);
+
+        let a = &usage["a"];
+        assert_eq!(a.tasks, 3);
+        assert_eq!(a.subrequests, 3);
+
+        let b = &usage["b"];
+        assert_eq!(b.tasks, 2);
+        assert_eq!(b.subrequests, 4);
+
+        for usage in [a, b] {
+            assert!(usage.wall_time > Duration::ZERO);
+            assert!(usage.peak_heap_used > 0);
+        }
+    });
+}
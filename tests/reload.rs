@@ -0,0 +1,119 @@
+mod common;
+
+use openworkers_runtime::Script;
+use openworkers_runtime::Worker;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::script;
+use common::worker;
+
+const ENV_WORKER: &str = r#"
+addEventListener("fetch", (event) => {
+  event.respondWith(new Response(`v2 ${JSON.stringify(env)}`));
+});
+"#;
+
+fn script_with_env(code: &str, env: &str) -> Script {
+    Script {
+        env: Some(env.to_string()),
+        ..script(code)
+    }
+}
+
+#[test]
+fn reload_replaces_the_handler() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            let count = 0;
+            addEventListener("fetch", (event) => {
+              event.respondWith(new Response(`v1 ${++count}`));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        assert_eq!(fetch(&mut worker, get("/")).await.text(), "v1 1");
+
+        worker
+            .reload(script(
+                r#"
+                let count = 0;
+                addEventListener("fetch", (event) => {
+                  event.respondWith(new Response(`v2 ${++count}`));
+                });
+                "#,
+            ))
+            .await
+            .unwrap();
+
+        // Only the new handler runs, with its own module state
+        let res = fetch(&mut worker, get("/")).await;
+        res.assert_ok();
+        assert_eq!(res.text(), "v2 1");
+    });
+}
+
+#[test]
+fn reload_replaces_the_env() {
+    run(async {
+        let mut worker = Worker::new(
+            script_with_env(
+                r#"
+                addEventListener("fetch", (event) => {
+                  event.respondWith(new Response(`v1 ${JSON.stringify(env)}`));
+                });
+                "#,
+                r#"{"NAME":"before"}"#,
+            ),
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            fetch(&mut worker, get("/")).await.text(),
+            r#"v1 {"NAME":"before"}"#
+        );
+
+        worker
+            .reload(script_with_env(
+                ENV_WORKER,
+                r#"{"NAME":"after \"quoted\""}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fetch(&mut worker, get("/")).await.text(),
+            r#"v2 {"NAME":"after \"quoted\""}"#
+        );
+
+        // Without env, the current one is kept
+        worker.reload(script(ENV_WORKER)).await.unwrap();
+
+        assert_eq!(
+            fetch(&mut worker, get("/")).await.text(),
+            r#"v2 {"NAME":"after \"quoted\""}"#
+        );
+    });
+}
+
+#[test]
+fn reload_rejects_an_invalid_env() {
+    run(async {
+        let mut worker = worker(ENV_WORKER, Default::default()).await;
+
+        assert!(worker
+            .reload(script_with_env(ENV_WORKER, "[1, 2]"))
+            .await
+            .is_err());
+
+        // The worker is left untouched
+        assert_eq!(fetch(&mut worker, get("/")).await.text(), "v2 undefined");
+    });
+}
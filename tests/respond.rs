@@ -0,0 +1,561 @@
+mod common;
+
+use bytes::Bytes;
+use openworkers_runtime::ContentLengthPolicy;
+use openworkers_runtime::FetchInit;
+use openworkers_runtime::InvalidUtf8Policy;
+use openworkers_runtime::ResponseBody;
+use openworkers_runtime::Task;
+use openworkers_runtime::Worker;
+use openworkers_runtime::WorkerOptions;
+use tokio::sync::oneshot;
+
+use common::fetch;
+use common::get;
+use common::logs;
+use common::read_body;
+use common::request;
+use common::run;
+use common::worker;
+use common::worker_with_logs;
+
+/// Responds with an invalid UTF-8 text body, the error of `respondWith` if
+/// any is served on `/error`.
+const INVALID_UTF8_WORKER: &str = r#"
+  let error = "none";
+
+  addEventListener("fetch", (event) => {
+    if (new URL(event.request.url).pathname === "/error") {
+      event.respondWith(new Response(error));
+      return;
+    }
+
+    const body = new Uint8Array([0x68, 0x69, 0xff, 0xfe]);
+    const headers = { "content-type": "text/plain" };
+
+    event
+      .respondWith(new Response(body, { headers }))
+      .catch((err) => (error = err.message));
+  });
+"#;
+
+fn invalid_utf8_options(policy: InvalidUtf8Policy) -> WorkerOptions {
+    WorkerOptions {
+        invalid_utf8_policy: policy,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn invalid_utf8_is_sent_as_is_by_default() {
+    run(async {
+        let mut worker = worker(INVALID_UTF8_WORKER, Default::default()).await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.body(), [0x68, 0x69, 0xff, 0xfe]);
+    });
+}
+
+#[test]
+fn invalid_utf8_warn_policy_sends_the_body() {
+    run(async {
+        let options = invalid_utf8_options(InvalidUtf8Policy::Warn);
+        let mut worker = worker(INVALID_UTF8_WORKER, options).await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.body(), [0x68, 0x69, 0xff, 0xfe]);
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(res.text(), "none");
+    });
+}
+
+#[test]
+fn invalid_utf8_reject_policy_rejects_respond_with() {
+    run(async {
+        let options = invalid_utf8_options(InvalidUtf8Policy::Reject);
+        let mut worker = worker(INVALID_UTF8_WORKER, options).await;
+
+        // The worker didn't respond, the host gets the default response
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 500);
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert!(
+            res.text()
+                .starts_with("Text response body is not valid UTF-8"),
+            "{}",
+            res.text()
+        );
+    });
+}
+
+/// Serves the file named by the `file` query parameter or the path, the
+/// error of `respondWithFile` if any is served on `/error`.
+const FILE_WORKER: &str = r#"
+  let error = "none";
+
+  addEventListener("fetch", (event) => {
+    const url = new URL(event.request.url);
+
+    if (url.pathname === "/error") {
+      event.respondWith(new Response(error));
+      return;
+    }
+
+    try {
+      event.respondWithFile(url.searchParams.get("file") ?? url.pathname, {
+        status: Number(url.searchParams.get("status") ?? 200),
+        headers: { "content-type": "application/octet-stream" },
+      });
+    } catch (err) {
+      error = err.message;
+    }
+  });
+"#;
+
+#[test]
+fn file_response_is_streamed_by_the_host() {
+    let root = std::env::temp_dir().join(format!("openworkers-files-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("assets")).unwrap();
+
+    // Larger than one chunk of the file stream
+    let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(root.join("assets/app.bin"), &content).unwrap();
+
+    let outside = root.with_extension("txt");
+    std::fs::write(&outside, "secret").unwrap();
+
+    run(async {
+        let options = WorkerOptions {
+            file_response_root: Some(root.clone()),
+            ..Default::default()
+        };
+
+        let mut worker = worker(FILE_WORKER, options).await;
+
+        let (res_tx, res_rx) = oneshot::channel();
+        let task = Task::Fetch(Some(FetchInit::new(get("/assets/app.bin"), res_tx)));
+
+        worker.exec(task).await.unwrap();
+
+        let (parts, body) = res_rx.await.unwrap().into_parts();
+
+        match &body {
+            ResponseBody::File(path) => {
+                assert_eq!(path, &root.canonicalize().unwrap().join("assets/app.bin"))
+            }
+            body => panic!("expected a file body, got {body:?}"),
+        }
+
+        assert_eq!(parts.headers["content-type"], "application/octet-stream");
+
+        let (bytes, _) = read_body(body).await;
+        assert_eq!(bytes, content);
+
+        // Nothing is served outside of the root, the worker couldn't respond
+        let name = outside.file_name().unwrap().to_str().unwrap();
+        let res = fetch(&mut worker, get(&format!("/?file=../{name}"))).await;
+        assert_eq!(res.status(), 500);
+        assert!(res.body().is_empty());
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert!(
+            res.text().starts_with("Cannot respond with file"),
+            "{}",
+            res.text()
+        );
+    });
+
+    std::fs::remove_dir_all(&root).unwrap();
+    std::fs::remove_file(&outside).unwrap();
+}
+
+#[test]
+fn file_response_is_disabled_without_a_root() {
+    run(async {
+        let mut worker = worker(FILE_WORKER, Default::default()).await;
+
+        let res = fetch(&mut worker, get("/?file=/etc/hostname")).await;
+        assert_eq!(res.status(), 500);
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(res.text(), "File responses are not enabled");
+    });
+}
+
+#[test]
+fn file_response_status_is_validated() {
+    let root = std::env::temp_dir().join(format!("openworkers-status-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("file.txt"), "file").unwrap();
+
+    run(async {
+        let options = WorkerOptions {
+            file_response_root: Some(root.clone()),
+            ..Default::default()
+        };
+
+        let mut worker = worker(FILE_WORKER, options).await;
+
+        for status in [0, 42, 1000] {
+            let res = fetch(&mut worker, get(&format!("/file.txt?status={status}"))).await;
+            res.assert_ok();
+            assert_eq!(res.status(), 500);
+
+            let res = fetch(&mut worker, get("/error")).await;
+            assert_eq!(
+                res.text(),
+                format!("The status provided ({status}) is outside the range [200, 599]")
+            );
+        }
+
+        let res = fetch(&mut worker, get("/file.txt?status=404")).await;
+        res.assert_ok();
+        assert_eq!(res.status(), 404);
+        assert_eq!(res.text(), "file");
+    });
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn second_respond_with_throws_in_the_worker() {
+    run(async {
+        let (mut worker, log_rx) = worker_with_logs(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(new Response("first"));
+
+              try {
+                event.respondWith(new Response("second"));
+              } catch (err) {
+                console.log(`${err.name}: ${err.message}`);
+              }
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "first");
+        assert_eq!(logs(&log_rx), ["TypeError: Response already sent"]);
+    });
+}
+
+/// Responds "hello" buffered or streamed depending on the path, with the
+/// `Content-Length` given by the `len` query parameter if any. The error of
+/// `respondWith` if any is served on `/error`.
+const CONTENT_LENGTH_WORKER: &str = r#"
+  let error = "none";
+
+  addEventListener("fetch", (event) => {
+    const url = new URL(event.request.url);
+
+    if (url.pathname === "/error") {
+      event.respondWith(new Response(error));
+      return;
+    }
+
+    const len = url.searchParams.get("len");
+    const headers = len === null ? {} : { "content-length": len };
+
+    const body =
+      url.pathname === "/stream"
+        ? new ReadableStream({
+            start(controller) {
+              controller.enqueue(new TextEncoder().encode("hel"));
+              controller.enqueue(new TextEncoder().encode("lo"));
+              controller.close();
+            },
+          })
+        : "hello";
+
+    event
+      .respondWith(new Response(body, { headers }))
+      .catch((err) => (error = err.message));
+  });
+"#;
+
+fn content_length_options(policy: ContentLengthPolicy) -> WorkerOptions {
+    WorkerOptions {
+        content_length_policy: policy,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn content_length_matching_buffered_body() {
+    run(async {
+        let options = content_length_options(ContentLengthPolicy::Reject);
+        let mut worker = worker(CONTENT_LENGTH_WORKER, options).await;
+
+        let res = fetch(&mut worker, get("/buffered?len=5")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.header("content-length"), Some("5"));
+        assert_eq!(res.text(), "hello");
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(res.text(), "none");
+    });
+}
+
+#[test]
+fn content_length_mismatching_buffered_body_is_rejected() {
+    run(async {
+        let options = content_length_options(ContentLengthPolicy::Reject);
+        let mut worker = worker(CONTENT_LENGTH_WORKER, options).await;
+
+        // The worker couldn't respond, the host gets the default response
+        let res = fetch(&mut worker, get("/buffered?len=10")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 500);
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(
+            res.text(),
+            "Content-Length mismatch: declared 10, body is 5 bytes"
+        );
+    });
+}
+
+#[test]
+fn content_length_is_not_added_to_buffered_body() {
+    run(async {
+        let options = content_length_options(ContentLengthPolicy::Reject);
+        let mut worker = worker(CONTENT_LENGTH_WORKER, options).await;
+
+        let res = fetch(&mut worker, get("/buffered")).await;
+
+        res.assert_ok();
+        assert_eq!(res.header("content-length"), None);
+        assert_eq!(res.text(), "hello");
+    });
+}
+
+#[test]
+fn content_length_is_not_added_to_streamed_body() {
+    run(async {
+        let options = content_length_options(ContentLengthPolicy::Reject);
+        let mut worker = worker(CONTENT_LENGTH_WORKER, options).await;
+
+        let res = fetch(&mut worker, get("/stream")).await;
+
+        res.assert_ok();
+        assert!(res.streamed);
+        assert_eq!(res.header("content-length"), None);
+        assert_eq!(res.text(), "hello");
+    });
+}
+
+#[test]
+fn content_length_of_head_response_is_kept() {
+    run(async {
+        let options = content_length_options(ContentLengthPolicy::Reject);
+        let mut worker = worker(CONTENT_LENGTH_WORKER, options).await;
+
+        let (res_tx, res_rx) = oneshot::channel();
+        let req = request("HEAD", "/buffered?len=5", Bytes::new());
+
+        worker
+            .exec(Task::Fetch(Some(FetchInit::new(req, res_tx))))
+            .await
+            .unwrap();
+
+        let res = res_rx.await.unwrap();
+
+        assert_eq!(res.headers()["content-length"], "5");
+        assert!(matches!(res.body(), ResponseBody::None));
+    });
+}
+
+#[test]
+fn content_length_mismatch_is_allowed_by_default() {
+    run(async {
+        let mut worker = worker(CONTENT_LENGTH_WORKER, Default::default()).await;
+
+        let res = fetch(&mut worker, get("/buffered?len=10")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.header("content-length"), Some("10"));
+
+        let res = fetch(&mut worker, get("/stream?len=10")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "hello");
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(res.text(), "none");
+    });
+}
+
+#[test]
+fn content_length_matching_streamed_body() {
+    run(async {
+        let options = content_length_options(ContentLengthPolicy::Reject);
+        let mut worker = worker(CONTENT_LENGTH_WORKER, options).await;
+
+        let res = fetch(&mut worker, get("/stream?len=5")).await;
+
+        res.assert_ok();
+        assert!(res.streamed);
+        assert_eq!(res.text(), "hello");
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(res.text(), "none");
+    });
+}
+
+#[test]
+fn content_length_mismatching_streamed_body_is_rejected_at_the_end() {
+    run(async {
+        let options = content_length_options(ContentLengthPolicy::Reject);
+        let mut worker = worker(CONTENT_LENGTH_WORKER, options).await;
+
+        // The head was sent before the mismatch was found
+        let res = fetch(&mut worker, get("/stream?len=10")).await;
+
+        res.assert_ok();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.text(), "hello");
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(
+            res.text(),
+            "Content-Length mismatch: declared 10, body is 5 bytes"
+        );
+    });
+}
+
+#[test]
+fn content_length_mismatch_warn_policy_sends_the_response() {
+    run(async {
+        let options = content_length_options(ContentLengthPolicy::Warn);
+        let mut worker = worker(CONTENT_LENGTH_WORKER, options).await;
+
+        let res = fetch(&mut worker, get("/buffered?len=10")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "hello");
+
+        let res = fetch(&mut worker, get("/error")).await;
+        assert_eq!(res.text(), "none");
+    });
+}
+
+/// Execute a fetch task, returning the response as sent to the host.
+async fn raw_fetch(
+    worker: &mut Worker,
+    req: http_v02::Request<Bytes>,
+) -> http_v02::Response<ResponseBody> {
+    let (res_tx, res_rx) = oneshot::channel();
+
+    worker
+        .exec(Task::Fetch(Some(FetchInit::new(req, res_tx))))
+        .await
+        .unwrap();
+
+    res_rx.await.unwrap()
+}
+
+/// Responds with the status given by the path and no body.
+const STATUS_WORKER: &str = r#"
+  addEventListener("fetch", (event) => {
+    const status = Number(new URL(event.request.url).pathname.slice(1));
+    event.respondWith(new Response(status === 200 ? "hello" : null, { status }));
+  });
+"#;
+
+#[test]
+fn no_content_statuses_have_no_body() {
+    run(async {
+        let mut worker = worker(STATUS_WORKER, Default::default()).await;
+
+        for status in [204, 304] {
+            let res = raw_fetch(&mut worker, get(&format!("/{status}"))).await;
+
+            assert_eq!(res.status(), status);
+            assert!(matches!(res.body(), ResponseBody::None), "{status}");
+            assert!(!res.headers().contains_key("content-length"));
+        }
+    });
+}
+
+#[test]
+fn empty_body_is_distinct_from_no_body() {
+    run(async {
+        let mut worker = worker(STATUS_WORKER, Default::default()).await;
+
+        let res = raw_fetch(&mut worker, get("/404")).await;
+
+        assert_eq!(res.status(), 404);
+        assert!(matches!(res.body(), ResponseBody::Empty));
+    });
+}
+
+#[test]
+fn head_response_has_no_body() {
+    run(async {
+        let mut worker = worker(STATUS_WORKER, Default::default()).await;
+
+        let res = raw_fetch(&mut worker, request("HEAD", "/200", Bytes::new())).await;
+
+        assert_eq!(res.status(), 200);
+        assert!(matches!(res.body(), ResponseBody::None));
+
+        let res = raw_fetch(&mut worker, get("/200")).await;
+
+        assert!(matches!(res.body(), ResponseBody::Bytes(body) if body == "hello"));
+    });
+}
+
+#[test]
+fn head_stream_response_has_no_body() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              const body = new ReadableStream({
+                start(controller) {
+                  controller.enqueue(new TextEncoder().encode("hello"));
+                  controller.close();
+                },
+              });
+
+              event.respondWith(
+                new Response(body, { headers: { "content-length": "5" } }),
+                { trailers: { "x-trailer": "1" } }
+              );
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let res = raw_fetch(&mut worker, request("HEAD", "/", Bytes::new())).await;
+
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers()["content-length"], "5");
+        assert!(matches!(res.body(), ResponseBody::None));
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert!(res.streamed);
+        assert_eq!(res.text(), "hello");
+    });
+}
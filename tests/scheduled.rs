@@ -0,0 +1,245 @@
+mod common;
+
+use std::time::Duration;
+
+use openworkers_runtime::FetchInit;
+use openworkers_runtime::ScheduledInit;
+use openworkers_runtime::ScheduledOutcome;
+use openworkers_runtime::ScheduledOverlapPolicy;
+use openworkers_runtime::Task;
+use openworkers_runtime::TaskType;
+use openworkers_runtime::Worker;
+use openworkers_runtime::WorkerOptions;
+use tokio::sync::oneshot;
+
+use common::get;
+use common::run;
+use common::script;
+use common::worker;
+
+fn scheduled(time: u64) -> (Task, oneshot::Receiver<ScheduledOutcome>) {
+    let (res_tx, res_rx) = oneshot::channel();
+
+    (
+        Task::Scheduled(Some(ScheduledInit::new(res_tx, time))),
+        res_rx,
+    )
+}
+
+/// Each task takes 300ms and retries after the number of tasks running when
+/// it started, itself included.
+const OVERLAP_WORKER: &str = r#"
+  let running = 0;
+
+  addEventListener("scheduled", (event) => {
+    running++;
+    event.retry({ delaySeconds: running });
+
+    event.waitUntil(
+      scheduler.wait(300).then(() => running--)
+    );
+  });
+"#;
+
+/// Start a first task and give up awaiting it while it's still running.
+async fn start_overlapping(worker: &mut Worker) -> oneshot::Receiver<ScheduledOutcome> {
+    let (task, res_rx) = scheduled(1);
+
+    let exec = tokio::time::timeout(Duration::from_millis(50), worker.exec(task)).await;
+    assert!(exec.is_err(), "first task completed too early");
+
+    res_rx
+}
+
+fn overlap_options(policy: ScheduledOverlapPolicy) -> WorkerOptions {
+    WorkerOptions {
+        scheduled_overlap: policy,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn overlapping_tasks_run_concurrently_by_default() {
+    run(async {
+        let mut worker = worker(OVERLAP_WORKER, Default::default()).await;
+
+        let first = start_overlapping(&mut worker).await;
+
+        let (task, second) = scheduled(2);
+        worker.exec(task).await.unwrap();
+
+        assert_eq!(first.await.unwrap().delay_secs, 1);
+        assert_eq!(second.await.unwrap().delay_secs, 2);
+    });
+}
+
+#[test]
+fn overlapping_task_is_queued() {
+    run(async {
+        let options = overlap_options(ScheduledOverlapPolicy::Queue);
+        let mut worker = worker(OVERLAP_WORKER, options).await;
+
+        let first = start_overlapping(&mut worker).await;
+
+        let (task, second) = scheduled(2);
+        worker.exec(task).await.unwrap();
+
+        assert_eq!(first.await.unwrap().delay_secs, 1);
+        assert_eq!(second.await.unwrap().delay_secs, 1);
+    });
+}
+
+#[test]
+fn overlapping_task_is_queued_despite_an_interval() {
+    run(async {
+        let options = overlap_options(ScheduledOverlapPolicy::Queue);
+
+        // The interval keeps the event loop alive forever
+        let mut worker = worker(
+            r#"
+            let interval;
+
+            addEventListener("scheduled", (event) => {
+              interval ??= setInterval(() => {}, 10);
+              event.waitUntil(scheduler.wait(300));
+            });
+            "#,
+            options,
+        )
+        .await;
+
+        let first = start_overlapping(&mut worker).await;
+
+        let (task, second) = scheduled(2);
+
+        let outcomes = async { (first.await, second.await) };
+
+        tokio::select! {
+            result = worker.exec(task) => panic!("exec completed with an interval: {result:?}"),
+            (first, second) = outcomes => {
+                first.unwrap();
+                second.unwrap();
+            }
+            _ = tokio::time::sleep(Duration::from_secs(5)) => panic!("queued task never ran"),
+        }
+    });
+}
+
+#[test]
+fn overlapping_task_is_rejected() {
+    run(async {
+        let options = overlap_options(ScheduledOverlapPolicy::Reject);
+        let mut worker = worker(OVERLAP_WORKER, options).await;
+
+        // The first task keeps running
+        start_overlapping(&mut worker).await;
+
+        let (task, second) = scheduled(2);
+        let err = worker.exec(task).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "1 scheduled task(s) still running");
+        assert!(second.await.is_err());
+    });
+}
+
+#[test]
+fn failed_task_does_not_block_the_next_one() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("scheduled", (event) => {
+              switch (event.scheduledTime) {
+                case 0:
+                  throw new Error("task failed");
+                case 1:
+                  // Never calls waitUntil
+                  return;
+                default:
+                  event.waitUntil(Promise.resolve());
+              }
+            });
+            "#,
+            overlap_options(ScheduledOverlapPolicy::Reject),
+        )
+        .await;
+
+        let (task, res_rx) = scheduled(0);
+        let err = worker.exec(task).await.unwrap_err();
+        assert!(err.to_string().contains("task failed"), "{err}");
+        assert!(res_rx.await.is_err());
+
+        let (task, res_rx) = scheduled(1);
+        worker.exec(task).await.unwrap();
+        assert!(res_rx.await.is_err());
+
+        // Neither is still counted as running
+        let (task, res_rx) = scheduled(2);
+        worker.exec(task).await.unwrap();
+        assert_eq!(res_rx.await.unwrap(), ScheduledOutcome::default());
+    });
+}
+
+#[test]
+fn scheduled_only_worker() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("scheduled", (event) => {
+              event.waitUntil(Promise.resolve());
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        assert_eq!(worker.describe(), [TaskType::Scheduled]);
+
+        let (task, res_rx) = scheduled(1);
+        worker.exec(task).await.unwrap();
+        res_rx.await.unwrap();
+
+        // Refused before reaching the js realm
+        let (res_tx, res_rx) = oneshot::channel();
+        let task = Task::Fetch(Some(FetchInit::new(get("/"), res_tx)));
+
+        let err = worker.exec(task).await.unwrap_err();
+        assert_eq!(err.to_string(), "no fetch event listener registered");
+        assert!(res_rx.await.is_err());
+    });
+}
+
+#[test]
+fn describe_follows_reload() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              event.respondWith(new Response());
+            });
+            addEventListener("scheduled", (event) => {
+              event.waitUntil(Promise.resolve());
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        assert_eq!(worker.describe(), [TaskType::Fetch, TaskType::Scheduled]);
+
+        worker
+            .reload(script(
+                r#"
+                addEventListener("fetch", (event) => {
+                  event.respondWith(new Response());
+                });
+                "#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(worker.describe(), [TaskType::Fetch]);
+
+        let (task, _) = scheduled(1);
+        assert!(worker.exec(task).await.is_err());
+    });
+}
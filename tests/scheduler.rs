@@ -0,0 +1,68 @@
+mod common;
+
+use std::time::Duration;
+use std::time::Instant;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::worker;
+
+#[test]
+fn aborted_wait_rejects_promptly() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              const controller = new AbortController();
+              setTimeout(() => controller.abort(), 100);
+
+              const waited = scheduler
+                .wait(5000, { signal: controller.signal })
+                .then(() => "resolved", (err) => `${err.name} ${err instanceof DOMException}`);
+
+              event.respondWith(waited.then((result) => new Response(result)));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let start = Instant::now();
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "AbortError true");
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(1), "{elapsed:?}");
+    });
+}
+
+#[test]
+fn already_aborted_wait_rejects_with_the_reason() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              const signal = AbortSignal.abort(new Error("stop"));
+
+              const waited = scheduler
+                .wait(5000, { signal })
+                .then(() => "resolved", (err) => err.message);
+
+              event.respondWith(waited.then((result) => new Response(result)));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let start = Instant::now();
+        let res = fetch(&mut worker, get("/")).await;
+
+        assert_eq!(res.text(), "stop");
+        assert!(start.elapsed() < Duration::from_secs(1));
+    });
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use openworkers_runtime::init_v8_flags;
+use openworkers_runtime::set_semi_space_size_mb;
+use openworkers_runtime::set_stack_size_kb;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::worker;
+
+// V8 flags are process wide, this binary holds a single test
+#[test]
+fn stack_size_sets_the_process_flags() {
+    set_stack_size_kb(512).unwrap();
+
+    // Same flags, nothing to apply
+    set_stack_size_kb(512).unwrap();
+    init_v8_flags(vec!["--stack-size=512".to_string()]).unwrap();
+
+    let err = set_semi_space_size_mb(16).unwrap_err();
+    assert!(err.to_string().starts_with("V8 flags already set"), "{err}");
+
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              const recurse = (n) => recurse(n + 1) + 1;
+
+              try {
+                recurse(0);
+                event.respondWith(new Response("no overflow"));
+              } catch (err) {
+                event.respondWith(new Response(err.constructor.name));
+              }
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "RangeError");
+    });
+}
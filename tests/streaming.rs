@@ -0,0 +1,232 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::HttpResponse;
+use bytes::Bytes;
+use futures::stream::unfold;
+use openworkers_runtime::FetchInit;
+use openworkers_runtime::ResponseBody;
+use openworkers_runtime::RuntimeLimits;
+use openworkers_runtime::Task;
+use openworkers_runtime::WorkerOptions;
+use tokio::sync::oneshot;
+
+use common::fetch;
+use common::get;
+use common::read_body;
+use common::run;
+use common::upstream;
+use common::worker;
+
+/// Upstream streaming the given chunks, waiting `delay` before each one.
+fn chunked_upstream(chunks: &'static [&'static str], delay: Duration) -> String {
+    upstream(move |_, _| async move {
+        HttpResponse::Ok().streaming(unfold(0, move |i| async move {
+            let chunk = chunks.get(i)?;
+            tokio::time::sleep(delay).await;
+            Some((Ok::<_, actix_web::Error>(Bytes::from(*chunk)), i + 1))
+        }))
+    })
+}
+
+#[test]
+fn fan_in_pipes_upstreams_in_order() {
+    // The first upstream is the slowest, its body must still come first
+    let slow = chunked_upstream(&["a1,", "a2,"], Duration::from_millis(100));
+    let fast = chunked_upstream(&["b1,", "b2"], Duration::ZERO);
+
+    run(async {
+        let mut worker = worker(
+            &format!(
+                r#"
+                const upstreams = ["{slow}/a", "{fast}/b"];
+
+                addEventListener("fetch", (event) => {{
+                  const responses = upstreams.map((url) => fetch(url));
+                  const {{ readable, writable }} = new TransformStream();
+
+                  (async () => {{
+                    for (const response of responses) {{
+                      const {{ body }} = await response;
+                      await body.pipeTo(writable, {{ preventClose: true }});
+                    }}
+
+                    await writable.close();
+                  }})();
+
+                  event.respondWith(new Response(readable));
+                }});
+                "#
+            ),
+            Default::default(),
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert!(res.streamed);
+        assert_eq!(res.text(), "a1,a2,b1,b2");
+    });
+}
+
+#[test]
+fn transform_stream_is_delivered_chunk_by_chunk() {
+    let url = chunked_upstream(
+        &["hello ", "streaming ", "world"],
+        Duration::from_millis(100),
+    );
+
+    run(async {
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith(fetch("{url}").then((response) => {{
+                    const decoder = new TextDecoder();
+                    const encoder = new TextEncoder();
+
+                    const upper = new TransformStream({{
+                      transform(chunk, controller) {{
+                        const text = decoder.decode(chunk, {{ stream: true }});
+                        controller.enqueue(encoder.encode(text.toUpperCase()));
+                      }},
+                    }});
+
+                    return new Response(response.body.pipeThrough(upper));
+                  }}));
+                }});
+                "#
+            ),
+            Default::default(),
+        )
+        .await;
+
+        let (res_tx, res_rx) = tokio::sync::oneshot::channel();
+        let task = Task::Fetch(Some(FetchInit::new(get("/"), res_tx)));
+
+        let receive = async {
+            let mut stream = match res_rx.await.unwrap().into_body() {
+                ResponseBody::Stream(stream) => stream,
+                body => panic!("expected a streamed body, got {body:?}"),
+            };
+
+            let mut chunks = vec![];
+
+            while let Some(chunk) = stream.recv().await {
+                chunks.push((tokio::time::Instant::now(), chunk));
+            }
+
+            chunks
+        };
+
+        let (result, chunks) = tokio::join!(worker.exec(task), receive);
+
+        result.unwrap();
+
+        let body: Vec<u8> = chunks.iter().flat_map(|(_, c)| c.to_vec()).collect();
+        assert_eq!(body, b"HELLO STREAMING WORLD");
+
+        // The first chunk reached the host before the upstream was done
+        let (first, _) = chunks.first().unwrap();
+        let (last, _) = chunks.last().unwrap();
+        assert!(chunks.len() >= 3, "{} chunks", chunks.len());
+        assert!(*last - *first >= Duration::from_millis(150));
+    });
+}
+
+#[test]
+fn proxied_body_is_not_buffered_in_the_isolate() {
+    const CHUNKS: usize = 96;
+    const CHUNK_SIZE: usize = 1024 * 1024;
+
+    let url = upstream(|_, _| async {
+        HttpResponse::Ok().streaming(unfold(0, |i| async move {
+            (i < CHUNKS).then(|| {
+                let chunk = Bytes::from(vec![b'x'; CHUNK_SIZE]);
+                (Ok::<_, actix_web::Error>(chunk), i + 1)
+            })
+        }))
+    });
+
+    run(async {
+        // The body is three times larger than the whole heap
+        let options = WorkerOptions {
+            limits: RuntimeLimits {
+                heap_max_mb: Some(32),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut worker = worker(
+            &format!(
+                r#"
+                addEventListener("fetch", (event) => {{
+                  event.respondWith(fetch("{url}"));
+                }});
+                "#
+            ),
+            options,
+        )
+        .await;
+
+        let res = fetch(&mut worker, get("/")).await;
+
+        res.assert_ok();
+        assert!(res.streamed);
+        assert_eq!(res.body().len(), CHUNKS * CHUNK_SIZE);
+        assert!(!worker.memory_pressure());
+    });
+}
+
+#[test]
+fn shutdown_delivers_enqueued_chunks() {
+    run(async {
+        let mut worker = worker(
+            r#"
+            addEventListener("fetch", (event) => {
+              const body = new ReadableStream({
+                start(controller) {
+                  controller.enqueue(new TextEncoder().encode("a,"));
+                  controller.enqueue(new TextEncoder().encode("b,"));
+
+                  setTimeout(() => {
+                    controller.enqueue(new TextEncoder().encode("c"));
+                    controller.close();
+                  }, 100);
+                },
+              });
+
+              event.respondWith(new Response(body));
+            });
+            "#,
+            Default::default(),
+        )
+        .await;
+
+        let (res_tx, res_rx) = oneshot::channel();
+        let task = Task::Fetch(Some(FetchInit::new(get("/"), res_tx)));
+
+        // The host stops driving the task once the response head arrived
+        let res = {
+            let exec = worker.exec(task);
+            tokio::pin!(exec);
+
+            tokio::select! {
+                biased;
+                res = res_rx => res.unwrap(),
+                _ = &mut exec => panic!("task completed before its stream ended"),
+            }
+        };
+
+        let (shutdown, (body, _)) = tokio::join!(
+            worker.shutdown(Duration::from_secs(5)),
+            read_body(res.into_body())
+        );
+
+        shutdown.unwrap();
+        assert_eq!(body, "a,b,c");
+    });
+}
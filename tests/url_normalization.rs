@@ -0,0 +1,141 @@
+mod common;
+
+use openworkers_runtime::StaticRoute;
+use openworkers_runtime::TrailingSlash;
+use openworkers_runtime::UrlNormalization;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::worker;
+
+/// Echoes the request path as seen by the worker.
+const ECHO_WORKER: &str = r#"
+addEventListener("fetch", (event) => {
+  const { pathname, search } = new URL(event.request.url);
+  event.respondWith(new Response(`worker ${pathname}${search}`));
+});
+"#;
+
+/// Path seen by the host for each request path: `static` if it matched the
+/// static `/a/c` route, the path seen by the worker otherwise.
+///
+/// The JS `URL` resolves dot segments itself, the static route tells whether
+/// the host resolved them before matching.
+async fn served(normalization: UrlNormalization, paths: &[&str]) -> Vec<String> {
+    let options = WorkerOptions {
+        url_normalization: normalization,
+        static_routes: vec![StaticRoute::new("/a/c", 200, "static")],
+        ..Default::default()
+    };
+
+    let mut worker = worker(ECHO_WORKER, options).await;
+
+    let mut served = Vec::new();
+
+    for path in paths {
+        let res = fetch(&mut worker, get(path)).await;
+        res.assert_ok();
+        served.push(res.text());
+    }
+
+    served
+}
+
+#[test]
+fn paths_are_kept_by_default() {
+    run(async {
+        let served = served(
+            UrlNormalization::default(),
+            &["/a/./b/../c", "/docs/", "/docs", "/"],
+        )
+        .await;
+
+        assert_eq!(
+            served,
+            ["worker /a/c", "worker /docs/", "worker /docs", "worker /"]
+        );
+    });
+}
+
+#[test]
+fn dot_segments_are_resolved() {
+    run(async {
+        let normalization = UrlNormalization {
+            resolve_dot_segments: true,
+            ..Default::default()
+        };
+
+        let served = served(
+            normalization,
+            &["/a/./b/../c", "/../a/c", "/a/c/.?x=1", "/a/b/..", "/a/c/"],
+        )
+        .await;
+
+        assert_eq!(
+            served,
+            [
+                "static",
+                "static",
+                "worker /a/c/?x=1",
+                "worker /a/",
+                "worker /a/c/"
+            ]
+        );
+    });
+}
+
+#[test]
+fn trailing_slash_is_added() {
+    run(async {
+        let normalization = UrlNormalization {
+            trailing_slash: TrailingSlash::Add,
+            ..Default::default()
+        };
+
+        let served = served(normalization, &["/docs", "/docs/", "/docs?q=1", "/"]).await;
+
+        assert_eq!(
+            served,
+            [
+                "worker /docs/",
+                "worker /docs/",
+                "worker /docs/?q=1",
+                "worker /"
+            ]
+        );
+    });
+}
+
+#[test]
+fn trailing_slash_is_removed() {
+    run(async {
+        let normalization = UrlNormalization {
+            trailing_slash: TrailingSlash::Remove,
+            ..Default::default()
+        };
+
+        let served = served(normalization, &["/a/c/", "/docs//", "/docs?q=1", "/"]).await;
+
+        assert_eq!(
+            served,
+            ["static", "worker /docs", "worker /docs?q=1", "worker /"]
+        );
+    });
+}
+
+#[test]
+fn dot_segments_are_resolved_before_the_trailing_slash() {
+    run(async {
+        let normalization = UrlNormalization {
+            resolve_dot_segments: true,
+            trailing_slash: TrailingSlash::Remove,
+            ..Default::default()
+        };
+
+        let served = served(normalization, &["/a/b/..", "/a/b/../c/.", "/x/./"]).await;
+
+        assert_eq!(served, ["worker /a", "static", "worker /x"]);
+    });
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use openworkers_runtime::init_v8_flags;
+use openworkers_runtime::Worker;
+use openworkers_runtime::WorkerOptions;
+
+use common::fetch;
+use common::get;
+use common::run;
+use common::script;
+use common::worker;
+
+const HELLO_WORKER: &str = r#"
+  addEventListener("fetch", (event) => {
+    event.respondWith(new Response("hello"));
+  });
+"#;
+
+fn flags_options(flags: &[&str]) -> WorkerOptions {
+    WorkerOptions {
+        v8_flags: flags.iter().map(|flag| flag.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+// V8 flags are process wide, this binary holds a single test
+#[test]
+fn jitless_worker_serves_fetch() {
+    run(async {
+        let mut jitless = worker(HELLO_WORKER, flags_options(&["--jitless"])).await;
+
+        let res = fetch(&mut jitless, get("/")).await;
+
+        res.assert_ok();
+        assert_eq!(res.text(), "hello");
+
+        // Workers without flags keep the process ones
+        let mut plain = worker(HELLO_WORKER, Default::default()).await;
+        fetch(&mut plain, get("/")).await.assert_ok();
+
+        // Flags can't change once applied
+        let err = Worker::new(script(HELLO_WORKER), None, flags_options(&["--no-lazy"]))
+            .await
+            .err()
+            .expect("conflicting flags should be refused");
+
+        assert!(err.to_string().starts_with("V8 flags already set"), "{err}");
+
+        init_v8_flags(vec!["--jitless".to_string()]).unwrap();
+        init_v8_flags(vec![]).unwrap_err();
+    });
+}